@@ -0,0 +1,599 @@
+//! Full base backup and restore.
+//!
+//! `backup` writes every table's fields and records, plus the attachment
+//! files, into a directory with a versioned `manifest.json`. `restore`
+//! rebuilds the tables from it in the configured base: plain fields first,
+//! then links, then lookups and formulas (table and field ids remapped),
+//! re-uploads the attachments and recreates the records, filling link cells
+//! once every record has its new id.
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+
+use crate::config::Config;
+use crate::feishu::auth::FeishuAuth;
+use crate::feishu::bitable::{
+    BitableClient, FieldDef, FieldInfo, FieldType, RecordItem, RecordUpdate,
+};
+use crate::feishu::drive::{DriveClient, MediaParent};
+use crate::feishu::search::SearchOptions;
+use crate::prompt::confirm;
+use crate::models::bitable_value::BitableValue;
+
+const MANIFEST: &str = "manifest.json";
+/// Bump when the manifest layout changes; restore refuses newer versions
+const FORMAT_VERSION: u32 = 1;
+
+pub struct BackupOptions {
+    /// Backup directory (default: `<backup_dir>/<timestamp>`)
+    pub out: Option<PathBuf>,
+}
+
+pub struct RestoreOptions {
+    /// Directory holding `manifest.json`
+    pub dir: PathBuf,
+    /// Restore without asking for confirmation
+    pub yes: bool,
+    /// Delete existing tables with the same names once the restore succeeded
+    pub replace: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Manifest {
+    pub format_version: u32,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+    /// App the backup was taken from
+    pub app_token: String,
+    pub tables: Vec<TableBackup>,
+    /// Downloaded attachment files by original file_token
+    pub attachments: BTreeMap<String, AttachmentFile>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TableBackup {
+    pub table_id: String,
+    pub name: String,
+    /// Name of the table's first view
+    pub view: Option<String>,
+    pub fields: Vec<FieldInfo>,
+    pub records: Vec<RecordItem>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AttachmentFile {
+    /// Relative to the backup directory
+    pub path: String,
+    pub mime_type: Option<String>,
+}
+
+/// Old id -> new id for tables, fields, records and attachment tokens
+/// (their prefixes never collide)
+type IdMap = HashMap<String, String>;
+
+// ============================================================
+// Backup
+// ============================================================
+
+/// Dump every table of the base into a new backup directory
+pub async fn backup(config: &Config, opts: &BackupOptions) -> Result<PathBuf> {
+    let auth = FeishuAuth::from_config(config);
+    let client = BitableClient::new(auth.clone(), config.bitable_app_token.clone());
+    let drive = DriveClient::new(auth);
+
+    let now = chrono::Utc::now();
+    let dir = opts.out.clone().unwrap_or_else(|| {
+        config
+            .backup_dir
+            .join(now.format("%Y%m%d-%H%M%S").to_string())
+    });
+    anyhow::ensure!(
+        !dir.join(MANIFEST).exists(),
+        "{} already holds a backup",
+        dir.display()
+    );
+
+    let mut manifest = Manifest {
+        format_version: FORMAT_VERSION,
+        created_at: now,
+        app_token: config.bitable_app_token.clone(),
+        tables: Vec::new(),
+        attachments: BTreeMap::new(),
+    };
+
+    for table in client.list_tables().await? {
+        let fields = client.list_fields(&table.table_id).await?;
+        let view = client
+            .list_views(&table.table_id)
+            .await?
+            .into_iter()
+            .next()
+            .map(|v| v.view_name);
+        let records = client
+            .search_records(&table.table_id, &SearchOptions::default())
+            .await?;
+
+        for (name, value) in records.iter().flat_map(|r| &r.fields) {
+            let is_attachment = fields
+                .iter()
+                .any(|f| f.field_name == *name && f.field_type == FieldType::Attachment as i32);
+            if !is_attachment {
+                continue;
+            }
+            let value = BitableValue::from_json(value);
+            for file in value.as_attachments().unwrap_or_default() {
+                if manifest.attachments.contains_key(&file.file_token) {
+                    continue;
+                }
+                let path = format!(
+                    "attachments/{}/{}",
+                    file.file_token,
+                    file.name.replace(['/', '\\'], "_")
+                );
+                crate::video::download_image_attachment(&drive, &file.file_token, &dir.join(&path))
+                    .await
+                    .with_context(|| {
+                        format!(
+                            "Failed to download '{}' from table '{}'",
+                            file.name, table.name
+                        )
+                    })?;
+                manifest.attachments.insert(
+                    file.file_token.clone(),
+                    AttachmentFile {
+                        path,
+                        mime_type: file.mime_type.clone(),
+                    },
+                );
+            }
+        }
+
+        tracing::info!(
+            "Backed up table '{}' ({} records)",
+            table.name,
+            records.len()
+        );
+        manifest.tables.push(TableBackup {
+            table_id: table.table_id,
+            name: table.name,
+            view,
+            fields,
+            records,
+        });
+    }
+
+    std::fs::create_dir_all(&dir).with_context(|| format!("Failed to create {}", dir.display()))?;
+    let path = dir.join(MANIFEST);
+    std::fs::write(&path, serde_json::to_vec_pretty(&manifest)?)
+        .with_context(|| format!("Failed to write {}", path.display()))?;
+
+    println!(
+        "Backed up {} tables, {} records and {} attachments to {}",
+        manifest.tables.len(),
+        manifest
+            .tables
+            .iter()
+            .map(|t| t.records.len())
+            .sum::<usize>(),
+        manifest.attachments.len(),
+        dir.display()
+    );
+    Ok(dir)
+}
+
+fn load_manifest(dir: &Path) -> Result<Manifest> {
+    let path = dir.join(MANIFEST);
+    let content = std::fs::read_to_string(&path)
+        .with_context(|| format!("Failed to read {}", path.display()))?;
+    let version: serde_json::Value = serde_json::from_str(&content)
+        .with_context(|| format!("Failed to parse {}", path.display()))?;
+    let version = version["format_version"].as_u64().unwrap_or(0);
+    anyhow::ensure!(
+        version as u32 <= FORMAT_VERSION,
+        "{} has format version {}, this build reads up to {}",
+        path.display(),
+        version,
+        FORMAT_VERSION
+    );
+    serde_json::from_str(&content).with_context(|| format!("Failed to parse {}", path.display()))
+}
+
+// ============================================================
+// Restore
+// ============================================================
+
+/// One- and two-way links, added once every table exists
+fn is_link(field_type: i32) -> bool {
+    field_type == FieldType::SingleLink as i32 || field_type == FieldType::DuplexLink as i32
+}
+
+/// Fields that can go into `create_table`: known types that reference no
+/// other table or field
+fn is_plain(field_type: i32) -> bool {
+    match FieldType::from_code(field_type) {
+        Some(FieldType::Lookup | FieldType::Formula) | None => false,
+        Some(_) => !is_link(field_type),
+    }
+}
+
+fn field_def(field: &FieldInfo, ids: &IdMap) -> FieldDef {
+    FieldDef {
+        field_name: field.field_name.clone(),
+        field_type: field.field_type,
+        property: field.property.as_ref().map(|p| remap_ids(p, ids)),
+    }
+}
+
+/// Recreate a backup's tables and records in the configured base
+pub async fn restore(config: &Config, opts: &RestoreOptions) -> Result<()> {
+    let manifest = load_manifest(&opts.dir)?;
+    let auth = FeishuAuth::from_config(config);
+    let client = BitableClient::new(auth.clone(), config.bitable_app_token.clone());
+    let drive = DriveClient::new(auth);
+
+    let clashes: Vec<_> = client
+        .list_tables()
+        .await?
+        .into_iter()
+        .filter(|t| manifest.tables.iter().any(|b| b.name == t.name))
+        .collect();
+    let clash_names: Vec<&str> = clashes.iter().map(|t| t.name.as_str()).collect();
+    anyhow::ensure!(
+        clashes.is_empty() || opts.replace,
+        "Tables already exist: {}. Rename them first, or pass --replace to delete them after restoring",
+        clash_names.join(", ")
+    );
+
+    println!(
+        "Restoring {} tables, {} records and {} attachments from the backup of {} taken {}",
+        manifest.tables.len(),
+        manifest
+            .tables
+            .iter()
+            .map(|t| t.records.len())
+            .sum::<usize>(),
+        manifest.attachments.len(),
+        manifest.app_token,
+        manifest.created_at.format("%Y-%m-%d %H:%M:%S UTC")
+    );
+    if !clashes.is_empty() {
+        println!(
+            "Afterwards these tables will be DELETED: {}",
+            clash_names.join(", ")
+        );
+    }
+    if !opts.yes && !confirm("Continue?")? {
+        println!("Aborted, nothing changed.");
+        return Ok(());
+    }
+
+    let mut ids = IdMap::new();
+
+    // Step 1: tables with their plain fields, primary field first
+    for table in &manifest.tables {
+        let mut plain: Vec<&FieldInfo> = table
+            .fields
+            .iter()
+            .filter(|f| is_plain(f.field_type))
+            .collect();
+        plain.sort_by_key(|f| f.is_primary != Some(true));
+        let defs: Vec<FieldDef> = plain.iter().map(|f| field_def(f, &ids)).collect();
+        let view = table.view.as_deref().unwrap_or("全部");
+        let table_id = client.create_table(&table.name, view, &defs).await?;
+        ids.insert(table.table_id.clone(), table_id);
+    }
+    map_field_ids(&client, &manifest, &mut ids).await?;
+
+    // Step 2: links. A two-way link creates its back field in the other
+    // table, so that side is skipped when it comes up.
+    for table in &manifest.tables {
+        for field in table.fields.iter().filter(|f| is_link(f.field_type)) {
+            if ids.contains_key(&field.field_id) {
+                continue;
+            }
+            let def = field_def(field, &ids);
+            let field_id = client.create_field(&ids[&table.table_id], &def).await?;
+            ids.insert(field.field_id.clone(), field_id);
+            if field.field_type == FieldType::DuplexLink as i32 {
+                map_field_ids(&client, &manifest, &mut ids).await?;
+            }
+        }
+    }
+
+    // Step 3: lookups, formulas and unknown types, best-effort like `setup`
+    for table in &manifest.tables {
+        let rest = table
+            .fields
+            .iter()
+            .filter(|f| !is_plain(f.field_type) && !is_link(f.field_type));
+        for field in rest {
+            let def = field_def(field, &ids);
+            match client.create_field(&ids[&table.table_id], &def).await {
+                Ok(field_id) => {
+                    ids.insert(field.field_id.clone(), field_id);
+                }
+                Err(e) => tracing::warn!(
+                    "Could not recreate field '{}' in '{}': {}",
+                    field.field_name,
+                    table.name,
+                    e
+                ),
+            }
+        }
+    }
+
+    // Step 4: attachments
+    for (token, file) in &manifest.attachments {
+        let parent = match &file.mime_type {
+            Some(mime) if mime.starts_with("image/") => MediaParent::BitableImage,
+            _ => MediaParent::BitableFile,
+        };
+        let path = opts.dir.join(&file.path);
+        let new_token = drive
+            .upload_file(&path, parent, &config.bitable_app_token)
+            .await
+            .with_context(|| format!("Failed to upload {}", path.display()))?;
+        ids.insert(token.clone(), new_token);
+    }
+
+    // Step 5: records without their links
+    for table in &manifest.tables {
+        let records: Vec<serde_json::Value> = table
+            .records
+            .iter()
+            .map(|r| serde_json::json!({"fields": writable_fields(table, r, &ids, false)}))
+            .collect();
+        let created = client
+            .batch_create_records(&ids[&table.table_id], &records)
+            .await?;
+        for (old, new) in table.records.iter().zip(created) {
+            ids.insert(old.record_id.clone(), new.record_id);
+        }
+    }
+
+    // Step 6: link cells, now that every record has its new id
+    for table in &manifest.tables {
+        let updates: Vec<RecordUpdate> = table
+            .records
+            .iter()
+            .filter_map(|r| {
+                let fields = writable_fields(table, r, &ids, true);
+                let record_id = ids.get(&r.record_id)?.clone();
+                (!fields.is_empty()).then_some(RecordUpdate { record_id, fields })
+            })
+            .collect();
+        if !updates.is_empty() {
+            client
+                .batch_update_records(&ids[&table.table_id], &updates)
+                .await?;
+        }
+    }
+
+    for table in &clashes {
+        match client.delete_table(&table.table_id).await {
+            Ok(_) => tracing::info!(
+                "Deleted replaced table: {} ({})",
+                table.name,
+                table.table_id
+            ),
+            Err(e) => tracing::warn!("Could not delete table {}: {}", table.table_id, e),
+        }
+    }
+
+    println!("\nRestore complete. New table ids:\n");
+    for table in &manifest.tables {
+        println!("{} = {}", table.name, ids[&table.table_id]);
+    }
+    println!("\nUpdate any TABLE_ID_* in .env.txt that pointed at the old tables.");
+    Ok(())
+}
+
+/// Record the new field ids of every restored table, matched by name
+async fn map_field_ids(client: &BitableClient, manifest: &Manifest, ids: &mut IdMap) -> Result<()> {
+    for table in &manifest.tables {
+        let created = client.list_fields(&ids[&table.table_id]).await?;
+        for old in &table.fields {
+            if let Some(new) = created.iter().find(|f| f.field_name == old.field_name) {
+                ids.insert(old.field_id.clone(), new.field_id.clone());
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Cells of `record` in write format: the link fields only when `links`,
+/// everything else writable otherwise
+fn writable_fields(
+    table: &TableBackup,
+    record: &RecordItem,
+    ids: &IdMap,
+    links: bool,
+) -> HashMap<String, serde_json::Value> {
+    table
+        .fields
+        .iter()
+        .filter(|f| is_link(f.field_type) == links)
+        .filter_map(|f| {
+            let value = record.fields.get(&f.field_name)?;
+            let value = writable_value(f.field_type, value, ids)?;
+            Some((f.field_name.clone(), value))
+        })
+        .collect()
+}
+
+/// Turn a cell as read back into what the write endpoints accept.
+/// `None` for empty cells and for computed or automatic fields.
+fn writable_value(
+    field_type: i32,
+    value: &serde_json::Value,
+    ids: &IdMap,
+) -> Option<serde_json::Value> {
+    use serde_json::json;
+
+    let v = BitableValue::from_json(value);
+    Some(match FieldType::from_code(field_type)? {
+        FieldType::Text | FieldType::SingleSelect | FieldType::Phone => json!(v.as_text()?),
+        FieldType::Number => json!(v.as_number()?),
+        FieldType::MultiSelect => json!(v.as_multi_select()?),
+        FieldType::DateTime => json!(v.as_datetime()?.timestamp_millis()),
+        FieldType::Checkbox => json!(v.as_bool()?),
+        FieldType::Person => {
+            let persons: Vec<_> = v
+                .as_persons()?
+                .iter()
+                .map(|p| json!({"id": p.id}))
+                .collect();
+            json!(persons)
+        }
+        FieldType::Url => match &v {
+            BitableValue::Url { text, link } => json!({"text": text, "link": link}),
+            _ => {
+                let link = v.as_url()?;
+                json!({"text": link, "link": link})
+            }
+        },
+        FieldType::Attachment => {
+            let files: Vec<_> = v
+                .as_attachments()?
+                .iter()
+                .filter_map(|a| ids.get(&a.file_token))
+                .map(|token| json!({"file_token": token}))
+                .collect();
+            if files.is_empty() {
+                return None;
+            }
+            json!(files)
+        }
+        FieldType::SingleLink | FieldType::DuplexLink => {
+            let records: Vec<&String> = v
+                .as_links()?
+                .iter()
+                .flat_map(|l| &l.record_ids)
+                .filter_map(|id| ids.get(id))
+                .collect();
+            if records.is_empty() {
+                return None;
+            }
+            json!(records)
+        }
+        FieldType::Lookup
+        | FieldType::Formula
+        | FieldType::CreatedTime
+        | FieldType::ModifiedTime => return None,
+    })
+}
+
+/// Replace every old table/field id inside a field property with its new id
+fn remap_ids(value: &serde_json::Value, ids: &IdMap) -> serde_json::Value {
+    use serde_json::Value;
+    match value {
+        Value::String(s) => Value::String(ids.iter().fold(s.clone(), |s, (old, new)| {
+            if s.contains(old.as_str()) {
+                s.replace(old.as_str(), new)
+            } else {
+                s
+            }
+        })),
+        Value::Array(items) => Value::Array(items.iter().map(|v| remap_ids(v, ids)).collect()),
+        Value::Object(obj) => Value::Object(
+            obj.iter()
+                .map(|(k, v)| (k.clone(), remap_ids(v, ids)))
+                .collect(),
+        ),
+        other => other.clone(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn converts_read_values_to_write_format() {
+        let ids = IdMap::from([
+            ("boxOld".to_string(), "boxNew".to_string()),
+            ("recOld".to_string(), "recNew".to_string()),
+        ]);
+        let write = |t: FieldType, v: serde_json::Value| writable_value(t as i32, &v, &ids);
+
+        assert_eq!(
+            write(
+                FieldType::Text,
+                json!([{"type": "text", "text": "花"}, {"type": "text", "text": "雕"}])
+            ),
+            Some(json!("花雕"))
+        );
+        assert_eq!(write(FieldType::Number, json!(12.5)), Some(json!(12.5)));
+        assert_eq!(
+            write(FieldType::MultiSelect, json!(["a", "b"])),
+            Some(json!(["a", "b"]))
+        );
+        assert_eq!(
+            write(FieldType::DateTime, json!(1700000000000i64)),
+            Some(json!(1700000000000i64))
+        );
+        assert_eq!(
+            write(FieldType::Person, json!([{"id": "ou_1", "name": "店长"}])),
+            Some(json!([{"id": "ou_1"}]))
+        );
+        assert_eq!(
+            write(
+                FieldType::Url,
+                json!({"text": "官网", "link": "https://example.com"})
+            ),
+            Some(json!({"text": "官网", "link": "https://example.com"}))
+        );
+        assert_eq!(
+            write(
+                FieldType::Attachment,
+                json!([{"file_token": "boxOld", "name": "a.png"}])
+            ),
+            Some(json!([{"file_token": "boxNew"}]))
+        );
+        assert_eq!(
+            write(
+                FieldType::SingleLink,
+                json!([{"record_ids": ["recOld", "recGone"], "text": "x"}])
+            ),
+            Some(json!(["recNew"]))
+        );
+        // Computed and automatic fields are not written
+        assert_eq!(
+            write(FieldType::Lookup, json!({"type": 1, "value": ["a"]})),
+            None
+        );
+        assert_eq!(
+            write(FieldType::ModifiedTime, json!(1700000000000i64)),
+            None
+        );
+        assert_eq!(writable_value(1005, &json!(3), &ids), None);
+    }
+
+    #[test]
+    fn remaps_table_and_field_ids_in_properties() {
+        let ids = IdMap::from([
+            ("tblOld".to_string(), "tblNew".to_string()),
+            ("fldOld".to_string(), "fldNew".to_string()),
+        ]);
+        let lookup = json!({
+            "target_field": "fldOld",
+            "filter_info": {"target_table": "tblOld"},
+            "formatter": "0.00",
+        });
+        assert_eq!(
+            remap_ids(&lookup, &ids),
+            json!({
+                "target_field": "fldNew",
+                "filter_info": {"target_table": "tblNew"},
+                "formatter": "0.00",
+            })
+        );
+        let formula = json!({"formula_expression": "bitable::$table[tblOld].$field[fldOld]*2"});
+        assert_eq!(
+            remap_ids(&formula, &ids)["formula_expression"],
+            "bitable::$table[tblNew].$field[fldNew]*2"
+        );
+    }
+}
@@ -0,0 +1,175 @@
+//! Which column each model field is read from.
+//!
+//! By default a model field is read from its schema.toml `name`, or from
+//! the first of its `aliases` the table has, so a base with renamed or
+//! English columns syncs unchanged. `COLUMN_<TABLE>_<MODEL>` (or a
+//! profile's `[profiles.x.tables.<table>.columns]`) names the column
+//! outright, e.g. `COLUMN_PRODUCTS_RETAIL_PRICE=售价`.
+
+use anyhow::Result;
+use std::collections::BTreeMap;
+
+use crate::config::{Config, TableKind};
+use crate::feishu::bitable::{BitableClient, FieldInfo};
+use crate::schema::table_spec;
+
+/// Column names by model field for one table
+#[derive(Debug, Clone)]
+pub struct ColumnMap {
+    kind: TableKind,
+    columns: BTreeMap<String, String>,
+    /// The table's columns, as listed when the map was resolved
+    available: Vec<String>,
+}
+
+/// Columns sync ignores and model fields it finds no column for
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct ColumnReport {
+    /// Columns in the table that no schema.toml field or override names
+    pub unknown: Vec<String>,
+    /// Model fields none of whose candidate columns exist
+    pub unmapped: Vec<String>,
+}
+
+impl ColumnMap {
+    /// Map every model field of `kind` to the override, else to the first
+    /// of its name and aliases in `available` (else to its name, which the
+    /// parsers then find empty)
+    pub fn resolve(
+        kind: TableKind,
+        overrides: &BTreeMap<String, String>,
+        available: &[String],
+    ) -> Self {
+        let columns = table_spec(kind)
+            .fields
+            .iter()
+            .filter_map(|f| {
+                let model = f.model.as_ref()?;
+                let column = match overrides.get(model) {
+                    Some(column) => column.clone(),
+                    None => std::iter::once(&f.name)
+                        .chain(&f.aliases)
+                        .find(|name| available.contains(name))
+                        .unwrap_or(&f.name)
+                        .clone(),
+                };
+                Some((model.clone(), column))
+            })
+            .collect();
+        Self {
+            kind,
+            columns,
+            available: available.to_vec(),
+        }
+    }
+
+    /// Resolve against fields already listed, with the configured overrides
+    pub fn for_fields(config: &Config, kind: TableKind, fields: &[FieldInfo]) -> Self {
+        let available: Vec<String> = fields.iter().map(|f| f.field_name.clone()).collect();
+        Self::resolve(kind, &config.table(kind).columns, &available)
+    }
+
+    /// List the table's columns and resolve against them
+    pub async fn load(client: &BitableClient, config: &Config, kind: TableKind) -> Result<Self> {
+        let fields = client.list_fields(config.table_id(kind)).await?;
+        let map = Self::for_fields(config, kind, &fields);
+
+        let report = map.report();
+        if !report.unknown.is_empty() {
+            tracing::debug!(
+                "{:?}: columns not read by sync: {}",
+                kind,
+                report.unknown.join(", ")
+            );
+        }
+        for model in &report.unmapped {
+            tracing::debug!(
+                "{:?}: no column for '{}' (looked for '{}')",
+                kind,
+                model,
+                map.get(model)
+            );
+        }
+        Ok(map)
+    }
+
+    /// Column to read `model` from. Panics on a model key schema.toml
+    /// doesn't define, like `TableSpec::column`.
+    pub fn get(&self, model: &str) -> &str {
+        self.columns
+            .get(model)
+            .map(String::as_str)
+            .unwrap_or_else(|| panic!("schema.toml: {:?} has no field for '{}'", self.kind, model))
+    }
+
+    /// Column for the schema.toml field `name`: where its model field is
+    /// read from, or `name` itself for fields the parsers don't read
+    pub fn column_for<'a>(&'a self, name: &'a str) -> &'a str {
+        table_spec(self.kind)
+            .fields
+            .iter()
+            .find(|f| f.name == name)
+            .and_then(|f| f.model.as_deref())
+            .map_or(name, |model| self.get(model))
+    }
+
+    /// Mapped columns the table has, i.e. what the parsers will read
+    pub fn present(&self) -> Vec<&str> {
+        self.columns
+            .values()
+            .filter(|c| self.available.contains(c))
+            .map(String::as_str)
+            .collect()
+    }
+
+    pub fn report(&self) -> ColumnReport {
+        let spec = table_spec(self.kind);
+        let known = |column: &String| {
+            self.columns.values().any(|c| c == column)
+                || spec
+                    .fields
+                    .iter()
+                    .any(|f| f.name == *column || f.aliases.contains(column))
+        };
+        ColumnReport {
+            unknown: self
+                .available
+                .iter()
+                .filter(|c| !known(c))
+                .cloned()
+                .collect(),
+            unmapped: self
+                .columns
+                .iter()
+                .filter(|(_, column)| !self.available.contains(column))
+                .map(|(model, _)| model.clone())
+                .collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn aliases_and_overrides_pick_the_column() {
+        let available: Vec<String> = ["Product ID", "Price", "商品名称", "售价", "Notes"]
+            .iter()
+            .map(|s| s.to_string())
+            .collect();
+        let overrides = BTreeMap::from([("retail_price".to_string(), "售价".to_string())]);
+        let map = ColumnMap::resolve(TableKind::Products, &overrides, &available);
+
+        assert_eq!(map.get("id"), "Product ID");
+        assert_eq!(map.get("name"), "商品名称");
+        // The override wins over the "Price" alias
+        assert_eq!(map.get("retail_price"), "售价");
+        assert_eq!(map.get("stock"), "库存");
+
+        let report = map.report();
+        assert_eq!(report.unknown, vec!["Notes"]);
+        assert!(report.unmapped.contains(&"stock".to_string()));
+        assert!(!report.unmapped.contains(&"id".to_string()));
+    }
+}
@@ -1,61 +1,212 @@
 use anyhow::{Context, Result};
+use serde::Deserialize;
+use std::collections::BTreeMap;
 use std::path::PathBuf;
 
+use crate::feishu::auth::FeishuAuth;
+use crate::feishu::bitable::{BitableClient, TableInfo};
+use crate::profile::Settings;
+use crate::secret::{app_secret, Secret};
+
+/// Default Open API root (Feishu China). Lark international uses
+/// `https://open.larksuite.com/open-apis`.
+pub const DEFAULT_FEISHU_BASE_URL: &str = "https://open.feishu.cn/open-apis";
+
+/// Env file `Config::load` reads first (relative to the working directory)
+pub const ENV_FILE: &str = ".env.txt";
+
+/// The bitable tables that sync reads from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TableKind {
+    Products,
+    Brands,
+    DisplayCategories,
+    Media,
+    StoreInfo,
+    Slogans,
+}
+
+impl TableKind {
+    pub const ALL: [TableKind; 6] = [
+        TableKind::Products,
+        TableKind::Brands,
+        TableKind::DisplayCategories,
+        TableKind::Media,
+        TableKind::StoreInfo,
+        TableKind::Slogans,
+    ];
+
+    /// Suffix of the per-table env vars, e.g. `TABLE_ID_PRODUCTS`, `VIEW_PRODUCTS`
+    pub fn env_suffix(self) -> &'static str {
+        match self {
+            TableKind::Products => "PRODUCTS",
+            TableKind::Brands => "BRANDS",
+            TableKind::DisplayCategories => "DISPLAY_CATEGORIES",
+            TableKind::Media => "MEDIA",
+            TableKind::StoreInfo => "STORE_INFO",
+            TableKind::Slogans => "SLOGANS",
+        }
+    }
+
+    /// Optional tables are skipped by sync when not configured
+    pub fn is_optional(self) -> bool {
+        matches!(self, TableKind::Slogans)
+    }
+
+    /// Name `setup` gives the table (from schema.toml); used to find it
+    /// when no id is configured
+    pub fn default_name(self) -> &'static str {
+        &crate::schema::table_spec(self).name
+    }
+}
+
+/// Which Bitable app a token or link points at
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AppRef {
+    /// A Bitable app token (`/base/<token>`)
+    Base(String),
+    /// A wiki node hosting the Bitable (`/wiki/<token>`), resolved by `Config::resolve`
+    Wiki(String),
+}
+
+/// A Feishu link pasted in place of a token, e.g.
+/// `https://xxx.feishu.cn/base/bascnXXX?table=tblXXX&view=vewXXX`
+/// or `https://xxx.feishu.cn/wiki/wikcnXXX?table=tblXXX`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BitableLink {
+    pub app: AppRef,
+    pub table: Option<String>,
+    pub view: Option<String>,
+}
+
+impl BitableLink {
+    /// Parse a Feishu/Lark URL. Returns `None` for plain tokens/ids.
+    pub fn parse(input: &str) -> Result<Option<Self>> {
+        let input = input.trim();
+        if !input.starts_with("http://") && !input.starts_with("https://") {
+            return Ok(None);
+        }
+        let url = reqwest::Url::parse(input)
+            .with_context(|| format!("Invalid Feishu link '{}'", input))?;
+
+        let mut segments = url.path_segments().into_iter().flatten();
+        let app = match (segments.next(), segments.next()) {
+            (Some("base"), Some(token)) if !token.is_empty() => AppRef::Base(token.to_string()),
+            (Some("wiki"), Some(token)) if !token.is_empty() => AppRef::Wiki(token.to_string()),
+            _ => anyhow::bail!(
+                "Unrecognized Feishu link '{}': expected /base/<token> or /wiki/<token>",
+                input
+            ),
+        };
+        let query = |key: &str| {
+            url.query_pairs()
+                .find(|(k, _)| k == key)
+                .map(|(_, v)| v.into_owned())
+                .filter(|v| !v.is_empty())
+        };
+
+        Ok(Some(Self {
+            app,
+            table: query("table"),
+            view: query("view"),
+        }))
+    }
+}
+
+/// Per-table settings
+#[derive(Debug, Clone, Default)]
+pub struct TableConfig {
+    /// Table id (`tblXXX`); empty when not configured (see `Config::resolve_tables`)
+    pub id: String,
+    /// Table name to look up when no id is set (`TABLE_NAME_<KIND>`);
+    /// defaults to `TableKind::default_name`
+    pub name: Option<String>,
+    /// View to read through, by id (`vewXXX`) or by name (e.g. "全部商品").
+    /// Sync then honours that view's filters and ordering.
+    pub view: Option<String>,
+    /// Column overrides by model field (`COLUMN_<KIND>_<MODEL>`), see `columns`
+    pub columns: BTreeMap<String, String>,
+}
+
 #[derive(Debug, Clone)]
 pub struct Config {
+    /// Open API root used by auth, Bitable, Drive and Wiki calls
+    pub feishu_base_url: String,
     pub feishu_app_id: String,
-    pub feishu_app_secret: String,
+    /// `FEISHU_APP_SECRET`, or read via `FEISHU_APP_SECRET_FILE` /
+    /// `FEISHU_APP_SECRET_COMMAND`
+    pub feishu_app_secret: Secret,
+    /// Empty until `resolve` when the app was given as a wiki link
     pub bitable_app_token: String,
-    pub table_id_products: String,
-    pub table_id_brands: String,
-    pub table_id_display_categories: String,
-    pub table_id_media: String,
-    pub table_id_store_info: String,
-    pub table_id_slogans: String,
+    /// Wiki node still to be resolved to `bitable_app_token`
+    pub wiki_node: Option<String>,
+    pub tables: BTreeMap<TableKind, TableConfig>,
     /// Root of the family-business repo (auto-detected from binary location)
     pub repo_root: PathBuf,
+    /// Where incremental sync keeps its per-table snapshots
+    /// (`SYNC_STATE_DIR`, default `tools/bitable-sync/.sync-state`)
+    pub state_dir: PathBuf,
+    /// Where `backup` writes its archives
+    /// (`BACKUP_DIR`, default `tools/bitable-sync/backups`)
+    pub backup_dir: PathBuf,
+    /// Commit and push after sync (`SYNC_PUSH`, default true)
+    pub push: bool,
+    /// Where the values above came from
+    pub settings: Settings,
 }
 
 impl Config {
-    pub fn load() -> Result<Self> {
-        // Try loading .env.txt first, then .env
-        let _ = dotenvy::from_filename(".env.txt").or_else(|_| dotenvy::dotenv());
+    /// Read settings from the environment and the selected profile (or the
+    /// env file when there is no config file)
+    pub fn load(settings: Settings) -> Result<Self> {
+        let var = |key: &str| settings.get(key).map(str::to_string);
 
-        // Auto-detect repo root: binary is in tools/bitable-sync/, repo root is ../../
-        let repo_root = std::env::var("FAMILY_BUSINESS_REPO")
+        let repo_root = var("FAMILY_BUSINESS_REPO")
             .map(PathBuf::from)
-            .unwrap_or_else(|_| {
-                // Relative to working directory: tools/bitable-sync -> repo root
-                let cwd = std::env::current_dir().unwrap_or_else(|_| PathBuf::from("."));
-                // If cwd ends with tools/bitable-sync, go up two levels
-                if cwd.ends_with("tools/bitable-sync") {
-                    cwd.parent().unwrap().parent().unwrap().to_path_buf()
-                } else {
-                    // Fallback: try ../../ from the directory containing .env.txt
-                    cwd.join("../..").canonicalize().unwrap_or(cwd)
-                }
-            });
+            .unwrap_or_else(|| detect_repo_root().0);
+
+        let state_dir = var("SYNC_STATE_DIR")
+            .map(PathBuf::from)
+            .unwrap_or_else(|| repo_root.join("tools/bitable-sync/.sync-state"));
+        let backup_dir = var("BACKUP_DIR")
+            .map(PathBuf::from)
+            .unwrap_or_else(|| repo_root.join("tools/bitable-sync/backups"));
+        let push = match var("SYNC_PUSH") {
+            Some(v) => v
+                .parse()
+                .with_context(|| format!("SYNC_PUSH must be true or false, got '{}'", v))?,
+            None => true,
+        };
+
+        // BITABLE_APP_TOKEN may be a token or a link; table links can supply it too
+        let mut app = match var("BITABLE_APP_TOKEN") {
+            Some(v) => Some(match BitableLink::parse(&v)? {
+                Some(link) => link.app,
+                None => AppRef::Base(v),
+            }),
+            None => None,
+        };
+        let tables = load_tables(&settings, &mut app)?;
+        let (bitable_app_token, wiki_node) =
+            match app.context("BITABLE_APP_TOKEN not set (nor given by a TABLE_ID_* link)")? {
+                AppRef::Base(token) => (token, None),
+                AppRef::Wiki(node) => (String::new(), Some(node)),
+            };
 
         Ok(Self {
-            feishu_app_id: std::env::var("FEISHU_APP_ID")
-                .context("FEISHU_APP_ID not set")?,
-            feishu_app_secret: std::env::var("FEISHU_APP_SECRET")
-                .context("FEISHU_APP_SECRET not set")?,
-            bitable_app_token: std::env::var("BITABLE_APP_TOKEN")
-                .context("BITABLE_APP_TOKEN not set")?,
-            table_id_products: std::env::var("TABLE_ID_PRODUCTS")
-                .context("TABLE_ID_PRODUCTS not set")?,
-            table_id_brands: std::env::var("TABLE_ID_BRANDS")
-                .context("TABLE_ID_BRANDS not set")?,
-            table_id_display_categories: std::env::var("TABLE_ID_DISPLAY_CATEGORIES")
-                .context("TABLE_ID_DISPLAY_CATEGORIES not set")?,
-            table_id_media: std::env::var("TABLE_ID_MEDIA")
-                .context("TABLE_ID_MEDIA not set")?,
-            table_id_store_info: std::env::var("TABLE_ID_STORE_INFO")
-                .context("TABLE_ID_STORE_INFO not set")?,
-            table_id_slogans: std::env::var("TABLE_ID_SLOGANS")
-                .unwrap_or_default(),
+            feishu_base_url: var("FEISHU_BASE_URL")
+                .unwrap_or_else(|| DEFAULT_FEISHU_BASE_URL.to_string()),
+            feishu_app_id: var("FEISHU_APP_ID").context("FEISHU_APP_ID not set")?,
+            feishu_app_secret: app_secret(&settings)?,
+            bitable_app_token,
+            wiki_node,
+            tables,
             repo_root,
+            state_dir,
+            backup_dir,
+            push,
+            settings,
         })
     }
 
@@ -89,6 +240,81 @@ impl Config {
         Ok(())
     }
 
+    /// Resolve a wiki link to the Bitable app it hosts (no-op otherwise)
+    pub async fn resolve(&mut self) -> Result<()> {
+        let Some(node_token) = self.wiki_node.take() else {
+            return Ok(());
+        };
+        let auth = FeishuAuth::from_config(self);
+        let node = BitableClient::resolve_wiki_node(&auth, &node_token).await?;
+        anyhow::ensure!(
+            node.obj_type == "bitable",
+            "Wiki node '{}' ({}) is a {}, not a Bitable",
+            node_token,
+            node.title,
+            node.obj_type
+        );
+        self.bitable_app_token = node.obj_token;
+        Ok(())
+    }
+
+    /// Fill in table ids that were not configured by looking tables up by
+    /// name (`TABLE_NAME_<KIND>` or the name `setup` uses)
+    pub async fn resolve_tables(&mut self) -> Result<()> {
+        let available = self.resolve_existing_tables().await?;
+
+        for (kind, table) in &self.tables {
+            anyhow::ensure!(
+                !table.id.is_empty() || kind.is_optional(),
+                "No table named '{}' for {:?}; set TABLE_ID_{} or TABLE_NAME_{} (tables: {})",
+                table.name.as_deref().unwrap_or(kind.default_name()),
+                kind,
+                kind.env_suffix(),
+                kind.env_suffix(),
+                available
+                    .iter()
+                    .map(|t| t.name.as_str())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            );
+        }
+        Ok(())
+    }
+
+    /// Like `resolve_tables`, but tables that don't exist yet keep an empty
+    /// id (for `migrate`, which creates them). Returns the tables listed.
+    pub async fn resolve_existing_tables(&mut self) -> Result<Vec<TableInfo>> {
+        if self.tables.values().all(|t| !t.id.is_empty()) {
+            return Ok(Vec::new());
+        }
+        let auth = FeishuAuth::from_config(self);
+        let client = BitableClient::new(auth, self.bitable_app_token.clone());
+        let available = client.list_tables().await?;
+
+        for (kind, table) in self.tables.iter_mut() {
+            if !table.id.is_empty() {
+                continue;
+            }
+            let name = table.name.as_deref().unwrap_or(kind.default_name());
+            match find_table(&available, name)? {
+                Some(found) => {
+                    tracing::info!("Using table '{}' ({}) for {:?}", name, found.table_id, kind);
+                    table.id = found.table_id.clone();
+                }
+                None => tracing::debug!("No table named '{}' for {:?}", name, kind),
+            }
+        }
+        Ok(available)
+    }
+
+    pub fn table(&self, kind: TableKind) -> &TableConfig {
+        &self.tables[&kind]
+    }
+
+    pub fn table_id(&self, kind: TableKind) -> &str {
+        &self.table(kind).id
+    }
+
     pub fn data_dir(&self) -> PathBuf {
         self.repo_root.join("src/data")
     }
@@ -97,3 +323,233 @@ impl Config {
         self.repo_root.join("public")
     }
 }
+
+/// Repo root when `FAMILY_BUSINESS_REPO` is unset, with how it was found.
+/// The binary is run from tools/bitable-sync/, so the root is ../../
+pub fn detect_repo_root() -> (PathBuf, &'static str) {
+    let cwd = std::env::current_dir().unwrap_or_else(|_| PathBuf::from("."));
+    // If cwd ends with tools/bitable-sync, go up two levels
+    if cwd.ends_with("tools/bitable-sync") {
+        let root = cwd.parent().unwrap().parent().unwrap().to_path_buf();
+        return (root, "working directory is tools/bitable-sync");
+    }
+    // Fallback: try ../../ from the directory containing .env.txt
+    match cwd.join("../..").canonicalize() {
+        Ok(root) => (root, "../.. of the working directory"),
+        Err(_) => (cwd, "working directory (../.. does not exist)"),
+    }
+}
+
+/// Read `TABLE_ID_<KIND>` and `VIEW_<KIND>` for every table.
+///
+/// `TABLE_ID_<KIND>` may be a link carrying `?table=` (and optionally
+/// `&view=`, which `VIEW_<KIND>` overrides). The link's app fills in `app`
+/// when it is not set yet and must agree with it otherwise.
+fn load_tables(
+    settings: &Settings,
+    app: &mut Option<AppRef>,
+) -> Result<BTreeMap<TableKind, TableConfig>> {
+    let mut tables = BTreeMap::new();
+    for kind in TableKind::ALL {
+        let id_var = format!("TABLE_ID_{}", kind.env_suffix());
+        // Unset ids are looked up by name in `Config::resolve_tables`
+        let raw = settings.get(&id_var).unwrap_or_default().to_string();
+
+        let link = BitableLink::parse(&raw).with_context(|| format!("{} is invalid", id_var))?;
+        let (id, link_view) = match link {
+            Some(link) => {
+                match app {
+                    None => *app = Some(link.app),
+                    Some(existing) => anyhow::ensure!(
+                        !same_kind_differs(existing, &link.app),
+                        "{} links to {:?}, but the app is {:?}",
+                        id_var,
+                        link.app,
+                        existing
+                    ),
+                }
+                let table = link
+                    .table
+                    .with_context(|| format!("{} link has no ?table= parameter", id_var))?;
+                (table, link.view)
+            }
+            None => (raw, None),
+        };
+
+        let view = settings
+            .get(&format!("VIEW_{}", kind.env_suffix()))
+            .map(str::to_string)
+            .or(link_view);
+        let name = settings
+            .get(&format!("TABLE_NAME_{}", kind.env_suffix()))
+            .map(str::to_string);
+        let columns = crate::schema::table_spec(kind)
+            .fields
+            .iter()
+            .filter_map(|f| f.model.as_deref())
+            .filter_map(|model| {
+                let column = settings.get(&column_var(kind, model))?;
+                Some((model.to_string(), column.to_string()))
+            })
+            .collect();
+        tables.insert(
+            kind,
+            TableConfig {
+                id,
+                view,
+                name,
+                columns,
+            },
+        );
+    }
+    Ok(tables)
+}
+
+/// Env var naming the column `model` is read from, e.g. `COLUMN_PRODUCTS_RETAIL_PRICE`
+pub fn column_var(kind: TableKind, model: &str) -> String {
+    format!("COLUMN_{}_{}", kind.env_suffix(), model.to_uppercase())
+}
+
+/// Find the single table called `name` (surrounding whitespace ignored)
+fn find_table<'a>(tables: &'a [TableInfo], name: &str) -> Result<Option<&'a TableInfo>> {
+    let matches: Vec<&TableInfo> = tables.iter().filter(|t| t.name.trim() == name.trim()).collect();
+    match matches.as_slice() {
+        [] => Ok(None),
+        [one] => Ok(Some(one)),
+        many => anyhow::bail!(
+            "{} tables are named '{}' ({}); set the table id explicitly",
+            many.len(),
+            name,
+            many.iter()
+                .map(|t| t.table_id.as_str())
+                .collect::<Vec<_>>()
+                .join(", ")
+        ),
+    }
+}
+
+/// A base token and a wiki node can't be compared before resolving, so only
+/// refs of the same kind are checked
+fn same_kind_differs(a: &AppRef, b: &AppRef) -> bool {
+    match (a, b) {
+        (AppRef::Base(x), AppRef::Base(y)) | (AppRef::Wiki(x), AppRef::Wiki(y)) => x != y,
+        _ => false,
+    }
+}
+
+/// Set `values` in an env file, keeping every other line (secrets,
+/// comments) as is. Creates the file if needed.
+pub fn write_env_file(path: &std::path::Path, values: &[(String, String)]) -> Result<()> {
+    let content = match std::fs::read_to_string(path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => String::new(),
+        Err(e) => {
+            return Err(e).with_context(|| format!("Failed to read {}", path.display()))
+        }
+    };
+    std::fs::write(path, update_env(&content, values))
+        .with_context(|| format!("Failed to write {}", path.display()))
+}
+
+/// Replace the value of each `KEY=...` line in `values` (an `export `
+/// prefix is kept) and append the keys that are not there yet
+fn update_env(content: &str, values: &[(String, String)]) -> String {
+    let mut written = vec![false; values.len()];
+    let mut out = String::with_capacity(content.len());
+    for line in content.lines() {
+        let trimmed = line.trim_start();
+        let assignment = trimmed.strip_prefix("export ").unwrap_or(trimmed);
+        let key = assignment.split('=').next().unwrap_or_default().trim();
+        match values.iter().position(|(k, _)| k == key) {
+            Some(i) if assignment.contains('=') => {
+                let prefix = &line[..line.len() - assignment.len()];
+                out.push_str(&format!("{}{}={}\n", prefix, key, values[i].1));
+                written[i] = true;
+            }
+            _ => {
+                out.push_str(line);
+                out.push('\n');
+            }
+        }
+    }
+    for ((key, value), done) in values.iter().zip(written) {
+        if !done {
+            out.push_str(&format!("{}={}\n", key, value));
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_base_and_wiki_links() {
+        let link = BitableLink::parse(
+            "https://shop.feishu.cn/base/bascnAbc?table=tblProd&view=vewAll&from=share",
+        )
+        .unwrap()
+        .unwrap();
+        assert_eq!(link.app, AppRef::Base("bascnAbc".to_string()));
+        assert_eq!(link.table.as_deref(), Some("tblProd"));
+        assert_eq!(link.view.as_deref(), Some("vewAll"));
+
+        let wiki = BitableLink::parse("https://shop.larksuite.com/wiki/wikcnXyz")
+            .unwrap()
+            .unwrap();
+        assert_eq!(wiki.app, AppRef::Wiki("wikcnXyz".to_string()));
+        assert_eq!(wiki.table, None);
+    }
+
+    #[test]
+    fn plain_tokens_are_not_links() {
+        assert_eq!(BitableLink::parse("bascnAbc").unwrap(), None);
+        assert_eq!(BitableLink::parse("tblProd").unwrap(), None);
+    }
+
+    fn table(id: &str, name: &str) -> TableInfo {
+        TableInfo {
+            table_id: id.to_string(),
+            name: name.to_string(),
+            revision: None,
+        }
+    }
+
+    #[test]
+    fn finds_tables_by_name() {
+        let tables = vec![
+            table("tbl1", "商品表 Products"),
+            table("tbl2", "品牌表 Brands "),
+            table("tbl3", "旧表"),
+            table("tbl4", "旧表"),
+        ];
+        assert_eq!(
+            find_table(&tables, "品牌表 Brands").unwrap().unwrap().table_id,
+            "tbl2"
+        );
+        assert!(find_table(&tables, "标语表 Slogans").unwrap().is_none());
+
+        let err = find_table(&tables, "旧表").unwrap_err().to_string();
+        assert!(err.contains("tbl3, tbl4"), "{}", err);
+    }
+
+    #[test]
+    fn rejects_other_documents() {
+        let err = BitableLink::parse("https://shop.feishu.cn/docx/doxcnAbc").unwrap_err();
+        assert!(err.to_string().contains("expected /base/<token> or /wiki/<token>"));
+    }
+
+    #[test]
+    fn env_update_keeps_secrets_and_appends_new_keys() {
+        let before = "# Feishu app\nFEISHU_APP_SECRET=s3cret\nexport TABLE_ID_PRODUCTS=tblOld\n\nTABLE_ID_BRANDS_X=keep\n";
+        let values = vec![
+            ("TABLE_ID_PRODUCTS".to_string(), "tblNew".to_string()),
+            ("TABLE_ID_BRANDS".to_string(), "tblB".to_string()),
+        ];
+        assert_eq!(
+            update_env(before, &values),
+            "# Feishu app\nFEISHU_APP_SECRET=s3cret\nexport TABLE_ID_PRODUCTS=tblNew\n\nTABLE_ID_BRANDS_X=keep\nTABLE_ID_BRANDS=tblB\n"
+        );
+    }
+}
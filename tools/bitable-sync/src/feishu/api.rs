@@ -0,0 +1,597 @@
+use anyhow::Result;
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use std::time::Duration;
+
+use super::auth::FeishuAuth;
+
+// ============================================================
+// Response envelope and errors
+// ============================================================
+
+/// Standard Feishu Open API response envelope
+#[derive(Debug, Deserialize)]
+pub struct ApiResponse<T> {
+    pub code: i32,
+    pub msg: String,
+    pub data: Option<T>,
+}
+
+/// One page of a paginated list response
+#[derive(Debug, Deserialize)]
+pub struct Page<T> {
+    #[serde(default)]
+    pub has_more: bool,
+    pub page_token: Option<String>,
+    pub items: Option<Vec<T>>,
+}
+
+/// A Feishu call that failed after all retries (or with a fatal error)
+#[derive(Debug, thiserror::Error)]
+pub enum ApiError {
+    #[error("Failed to {context}: {code} - {msg}")]
+    Feishu {
+        context: String,
+        code: i32,
+        msg: String,
+        /// HTTP status the envelope came with
+        status: u16,
+    },
+    #[error("Failed to {context}: HTTP {status}: {body}")]
+    Http {
+        context: String,
+        status: u16,
+        body: String,
+    },
+}
+
+impl ApiError {
+    /// A record's cell could not be converted to its column's type, so the
+    /// batch was refused as a whole and nothing in it was written. Not when
+    /// the envelope came with a 5xx, which may follow a partial write.
+    pub fn is_record_rejection(&self) -> bool {
+        match self {
+            ApiError::Feishu { code, status, .. } => {
+                is_field_conversion_error(*code) && *status < 500
+            }
+            ApiError::Http { .. } => false,
+        }
+    }
+}
+
+/// Bitable's `*FieldConvFail` codes (text, number, select, date, checkbox,
+/// person, link, url, attachment, phone, ...)
+fn is_field_conversion_error(code: i32) -> bool {
+    (1254060..=1254074).contains(&code)
+}
+
+/// Whether a failed call is worth repeating
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorClass {
+    Retryable,
+    Fatal,
+}
+
+/// Classify a non-zero Feishu `code`
+pub fn classify_code(code: i32) -> ErrorClass {
+    match code {
+        // Frequency limit (app-wide)
+        99991400
+        // Bitable: too many requests, write conflict, data not ready, timeout
+        | 1254290 | 1254291 | 1254607 | 1255040
+        // Drive: transient failure, can retry
+        | 1061045 => ErrorClass::Retryable,
+        _ => ErrorClass::Fatal,
+    }
+}
+
+/// Codes meaning the tenant_access_token itself was rejected
+/// (missing, invalid, or expired early, e.g. after an app secret rotation)
+pub fn is_auth_error(code: i32) -> bool {
+    matches!(
+        code,
+        99991661 | 99991663 | 99991664 | 99991665 | 99991668 | 99991677
+    )
+}
+
+/// Classify an HTTP status returned without a usable Feishu envelope
+pub fn classify_status(status: reqwest::StatusCode) -> ErrorClass {
+    if status == reqwest::StatusCode::TOO_MANY_REQUESTS || status.is_server_error() {
+        ErrorClass::Retryable
+    } else {
+        ErrorClass::Fatal
+    }
+}
+
+// ============================================================
+// Retry policy
+// ============================================================
+
+/// Whether a request may be sent again after a failure that leaves it
+/// unclear if it was applied (a dropped connection, an unreadable
+/// response, a 5xx)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Idempotency {
+    /// Reads, updates, and creates carrying a `client_token`
+    Idempotent,
+    /// Writes Feishu can't deduplicate, e.g. creating a table, and deletes
+    /// (a replay of one that went through fails with not-found): only
+    /// retried when the server clearly turned them away (rate limits)
+    NonIdempotent,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct RetryPolicy {
+    /// Total attempts including the first one
+    pub max_attempts: u32,
+    pub base_delay: Duration,
+    pub max_delay: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 5,
+            base_delay: Duration::from_millis(500),
+            max_delay: Duration::from_secs(30),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Exponential backoff with jitter: a random delay in [d/2, d],
+    /// where d doubles each attempt up to `max_delay`
+    fn backoff(&self, attempt: u32) -> Duration {
+        let exp = self
+            .base_delay
+            .saturating_mul(1u32 << (attempt - 1).min(16))
+            .min(self.max_delay);
+        let half = exp.as_millis() as u64 / 2;
+        Duration::from_millis(half + fastrand::u64(0..=half))
+    }
+}
+
+/// Delay requested by the server via rate-limit headers, if any.
+/// Feishu sends `x-ogw-ratelimit-reset` (seconds); `Retry-After` is honoured too.
+fn rate_limit_delay(headers: &reqwest::header::HeaderMap) -> Option<Duration> {
+    ["x-ogw-ratelimit-reset", "retry-after"]
+        .iter()
+        .filter_map(|name| headers.get(*name))
+        .filter_map(|v| v.to_str().ok())
+        .filter_map(|v| v.trim().parse::<u64>().ok())
+        .map(Duration::from_secs)
+        .next()
+}
+
+// ============================================================
+// Client
+// ============================================================
+
+/// Shared request layer for all Feishu Open API calls.
+///
+/// Adds the tenant token, unwraps the response envelope, and retries
+/// rate limits and transient failures with backoff.
+#[derive(Clone)]
+pub struct FeishuApi {
+    auth: FeishuAuth,
+    client: reqwest::Client,
+    retry: RetryPolicy,
+}
+
+/// Outcome of a single attempt
+enum Attempt<T> {
+    Done(Option<T>),
+    /// The token was rejected; drop it and replay with a fresh one
+    Reauth {
+        error: anyhow::Error,
+        token: String,
+    },
+    Retry {
+        error: anyhow::Error,
+        delay: Option<Duration>,
+        /// The request may have been applied before it failed
+        outcome_unknown: bool,
+    },
+}
+
+impl FeishuApi {
+    pub fn new(auth: FeishuAuth) -> Self {
+        Self {
+            auth,
+            client: reqwest::Client::new(),
+            retry: RetryPolicy::default(),
+        }
+    }
+
+    #[allow(dead_code)]
+    pub fn with_retry(mut self, retry: RetryPolicy) -> Self {
+        self.retry = retry;
+        self
+    }
+
+    pub fn base_url(&self) -> &str {
+        self.auth.base_url()
+    }
+
+    /// Send a request and return the envelope's `data`.
+    ///
+    /// `build` is called once per attempt so the request can be replayed.
+    /// `context` describes the call for logs and errors, e.g.
+    /// "read records from table tblXXX".
+    pub async fn send<T, F>(
+        &self,
+        context: &str,
+        idempotency: Idempotency,
+        build: F,
+    ) -> Result<Option<T>>
+    where
+        T: DeserializeOwned,
+        F: Fn(&reqwest::Client) -> reqwest::RequestBuilder,
+    {
+        let mut attempt = 1;
+        let mut reauthed = false;
+        loop {
+            match self.attempt(context, &build).await? {
+                Attempt::Done(data) => return Ok(data),
+                Attempt::Reauth { error, token } => {
+                    if reauthed {
+                        return Err(error);
+                    }
+                    tracing::warn!("{}, re-authenticating and replaying once", error);
+                    self.auth.invalidate(&token).await;
+                    reauthed = true;
+                }
+                Attempt::Retry {
+                    error,
+                    delay,
+                    outcome_unknown,
+                } => {
+                    if outcome_unknown && idempotency == Idempotency::NonIdempotent {
+                        return Err(error.context(
+                            "Not retried: the request may already have been applied",
+                        ));
+                    }
+                    if attempt >= self.retry.max_attempts {
+                        return Err(error);
+                    }
+                    let delay = delay
+                        .unwrap_or_else(|| self.retry.backoff(attempt))
+                        .min(self.retry.max_delay);
+                    tracing::warn!(
+                        "{} (attempt {}/{}), retrying in {:.1}s",
+                        error,
+                        attempt,
+                        self.retry.max_attempts,
+                        delay.as_secs_f64()
+                    );
+                    tokio::time::sleep(delay).await;
+                    attempt += 1;
+                }
+            }
+        }
+    }
+
+    /// Fetch every page of a list endpoint and concatenate the items.
+    ///
+    /// `build` creates the request for one page (including any `page_size`);
+    /// the `page_token` query parameter is added here.
+    pub async fn paginate<T, F>(&self, context: &str, build: F) -> Result<Vec<T>>
+    where
+        T: DeserializeOwned,
+        F: Fn(&reqwest::Client) -> reqwest::RequestBuilder,
+    {
+        let mut all_items = Vec::new();
+        let mut page_token: Option<String> = None;
+
+        loop {
+            let page = self
+                .send::<Page<T>, _>(context, Idempotency::Idempotent, |c| match page_token {
+                    Some(ref pt) => build(c).query(&[("page_token", pt)]),
+                    None => build(c),
+                })
+                .await?;
+
+            let Some(page) = page else { break };
+            if let Some(items) = page.items {
+                all_items.extend(items);
+            }
+
+            match (page.has_more, page.page_token) {
+                (true, Some(next)) => page_token = Some(next),
+                (true, None) => {
+                    tracing::warn!("{}: has_more without page_token, stopping", context);
+                    break;
+                }
+                (false, _) => break,
+            }
+        }
+
+        Ok(all_items)
+    }
+
+    async fn attempt<T, F>(&self, context: &str, build: &F) -> Result<Attempt<T>>
+    where
+        T: DeserializeOwned,
+        F: Fn(&reqwest::Client) -> reqwest::RequestBuilder,
+    {
+        let token = self.auth.get_token().await?;
+
+        let resp = match build(&self.client).bearer_auth(&token).send().await {
+            Ok(resp) => resp,
+            Err(e) if e.is_builder() => {
+                return Err(anyhow::Error::new(e).context(format!("Failed to {}", context)))
+            }
+            Err(e) => {
+                // A failed connect never reached the server
+                let outcome_unknown = !e.is_connect();
+                return Ok(Attempt::Retry {
+                    error: anyhow::Error::new(e).context(format!("Failed to {}", context)),
+                    delay: None,
+                    outcome_unknown,
+                });
+            }
+        };
+
+        let status = resp.status();
+        let delay = rate_limit_delay(resp.headers());
+        let body = match resp.text().await {
+            Ok(body) => body,
+            Err(e) => {
+                return Ok(Attempt::Retry {
+                    error: anyhow::Error::new(e)
+                        .context(format!("Failed to read response to {}", context)),
+                    delay,
+                    outcome_unknown: true,
+                })
+            }
+        };
+
+        // Feishu usually answers errors with a JSON envelope even on 4xx/5xx
+        let parsed = serde_json::from_str::<ApiResponse<T>>(&body);
+        let error: anyhow::Error = match parsed {
+            Ok(resp) if is_auth_error(resp.code) => {
+                let error = ApiError::Feishu {
+                    context: context.to_string(),
+                    code: resp.code,
+                    msg: resp.msg,
+                    status: status.as_u16(),
+                };
+                return Ok(Attempt::Reauth {
+                    error: error.into(),
+                    token,
+                });
+            }
+            Ok(resp) if resp.code != 0 => {
+                let error = ApiError::Feishu {
+                    context: context.to_string(),
+                    code: resp.code,
+                    msg: resp.msg,
+                    status: status.as_u16(),
+                };
+                if classify_code(resp.code) == ErrorClass::Fatal
+                    && classify_status(status) == ErrorClass::Fatal
+                {
+                    return Err(error.into());
+                }
+                error.into()
+            }
+            Ok(resp) if status.is_success() => return Ok(Attempt::Done(resp.data)),
+            Err(e) if status.is_success() => {
+                return Err(anyhow::Error::new(e)
+                    .context(format!("Failed to parse response to {}", context)))
+            }
+            _ => {
+                let error = ApiError::Http {
+                    context: context.to_string(),
+                    status: status.as_u16(),
+                    body: body.chars().take(200).collect(),
+                };
+                if classify_status(status) == ErrorClass::Fatal {
+                    return Err(error.into());
+                }
+                error.into()
+            }
+        };
+
+        // A rate limit or busy answer means the write was turned away; a 5xx
+        // may have come after it was applied
+        Ok(Attempt::Retry {
+            error,
+            delay,
+            outcome_unknown: status.is_server_error(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::feishu::stub::{token_response, StubResponse, StubServer};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    fn fast_retry() -> RetryPolicy {
+        RetryPolicy {
+            max_attempts: 3,
+            base_delay: Duration::from_millis(1),
+            max_delay: Duration::from_millis(20),
+        }
+    }
+
+    #[test]
+    fn classifies_rate_limit_as_retryable() {
+        assert_eq!(classify_code(99991400), ErrorClass::Retryable);
+        assert_eq!(classify_code(1254290), ErrorClass::Retryable);
+        assert_eq!(classify_code(1254005), ErrorClass::Fatal);
+        assert_eq!(
+            classify_status(reqwest::StatusCode::SERVICE_UNAVAILABLE),
+            ErrorClass::Retryable
+        );
+        assert_eq!(
+            classify_status(reqwest::StatusCode::BAD_REQUEST),
+            ErrorClass::Fatal
+        );
+
+        let feishu = |code, status| ApiError::Feishu {
+            context: "write".to_string(),
+            code,
+            msg: String::new(),
+            status,
+        };
+        assert!(feishu(1254061, 200).is_record_rejection());
+        // A 5xx may have come after the write, and permissions fail every record
+        assert!(!feishu(1254061, 500).is_record_rejection());
+        assert!(!feishu(1254302, 403).is_record_rejection());
+    }
+
+    #[test]
+    fn backoff_grows_and_is_capped() {
+        let policy = RetryPolicy {
+            max_attempts: 10,
+            base_delay: Duration::from_millis(100),
+            max_delay: Duration::from_millis(1000),
+        };
+        let d1 = policy.backoff(1);
+        assert!(d1 >= Duration::from_millis(50) && d1 <= Duration::from_millis(100));
+        let d8 = policy.backoff(8);
+        assert!(d8 >= Duration::from_millis(500) && d8 <= Duration::from_millis(1000));
+    }
+
+    #[tokio::test]
+    async fn retries_frequency_limit_then_succeeds() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let counter = calls.clone();
+        let server = StubServer::start(move |req| {
+            if req.path.starts_with("/auth/") {
+                return token_response("t", 7200);
+            }
+            if counter.fetch_add(1, Ordering::SeqCst) == 0 {
+                StubResponse::json(serde_json::json!({"code": 99991400, "msg": "frequency limit"}))
+                    .status(429)
+                    .header("x-ogw-ratelimit-reset", "0")
+            } else {
+                StubResponse::json(serde_json::json!({"code": 0, "msg": "ok", "data": {"n": 1}}))
+            }
+        })
+        .await;
+
+        let auth = FeishuAuth::new(&server.base_url, "app".into(), "secret".into());
+        let api = FeishuApi::new(auth).with_retry(fast_retry());
+        let url = format!("{}/ping", server.base_url);
+        let data: Option<serde_json::Value> = api
+            .send("ping", Idempotency::Idempotent, |c| c.get(&url))
+            .await
+            .unwrap();
+
+        assert_eq!(data.unwrap()["n"], 1);
+        assert_eq!(server.count("/ping"), 2);
+    }
+
+    #[tokio::test]
+    async fn rejected_token_is_refreshed_and_request_replayed() {
+        let tokens = Arc::new(AtomicUsize::new(0));
+        let issued = tokens.clone();
+        let server = StubServer::start(move |req| {
+            if req.path.starts_with("/auth/") {
+                let n = issued.fetch_add(1, Ordering::SeqCst);
+                return token_response(&format!("t{}", n), 7200);
+            }
+            if req.header("authorization") == Some("Bearer t0") {
+                StubResponse::json(serde_json::json!({"code": 99991663, "msg": "invalid token"}))
+                    .status(400)
+            } else {
+                StubResponse::json(serde_json::json!({"code": 0, "msg": "ok", "data": {}}))
+            }
+        })
+        .await;
+
+        let auth = FeishuAuth::new(&server.base_url, "app".into(), "secret".into());
+        let api = FeishuApi::new(auth.clone()).with_retry(fast_retry());
+        let url = format!("{}/ping", server.base_url);
+        api.send::<serde_json::Value, _>("ping", Idempotency::Idempotent, |c| c.get(&url))
+            .await
+            .unwrap();
+
+        assert_eq!(server.count("/auth/"), 2);
+        assert_eq!(server.count("/ping"), 2);
+        assert_eq!(auth.get_token().await.unwrap(), "t1");
+    }
+
+    #[tokio::test]
+    async fn paginate_follows_page_tokens() {
+        let server = StubServer::start(|req| {
+            if req.path.starts_with("/auth/") {
+                return token_response("t", 7200);
+            }
+            let page = if req.path.contains("page_token=p2") {
+                serde_json::json!({"has_more": false, "items": [3]})
+            } else {
+                serde_json::json!({"has_more": true, "page_token": "p2", "items": [1, 2]})
+            };
+            StubResponse::json(serde_json::json!({"code": 0, "msg": "ok", "data": page}))
+        })
+        .await;
+
+        let auth = FeishuAuth::new(&server.base_url, "app".into(), "secret".into());
+        let api = FeishuApi::new(auth).with_retry(fast_retry());
+        let url = format!("{}/list", server.base_url);
+        let items: Vec<i32> = api
+            .paginate("list", |c| c.get(&url).query(&[("page_size", 2)]))
+            .await
+            .unwrap();
+
+        assert_eq!(items, vec![1, 2, 3]);
+        assert_eq!(server.count("/list"), 2);
+    }
+
+    #[tokio::test]
+    async fn fatal_code_is_not_retried() {
+        let server = StubServer::start(|req| {
+            if req.path.starts_with("/auth/") {
+                return token_response("t", 7200);
+            }
+            StubResponse::json(serde_json::json!({"code": 1254005, "msg": "table not found"}))
+        })
+        .await;
+
+        let auth = FeishuAuth::new(&server.base_url, "app".into(), "secret".into());
+        let api = FeishuApi::new(auth).with_retry(fast_retry());
+        let url = format!("{}/ping", server.base_url);
+        let err = api
+            .send::<serde_json::Value, _>("ping", Idempotency::Idempotent, |c| c.get(&url))
+            .await
+            .unwrap_err();
+
+        assert!(err.to_string().contains("1254005"));
+        assert_eq!(server.count("/ping"), 1);
+    }
+
+    #[tokio::test]
+    async fn non_idempotent_write_is_not_replayed_after_a_server_error() {
+        let server = StubServer::start(|req| {
+            if req.path.starts_with("/auth/") {
+                return token_response("t", 7200);
+            }
+            StubResponse::json(serde_json::json!({"code": 0, "msg": "internal error"})).status(500)
+        })
+        .await;
+
+        let auth = FeishuAuth::new(&server.base_url, "app".into(), "secret".into());
+        let api = FeishuApi::new(auth).with_retry(fast_retry());
+        let url = format!("{}/create", server.base_url);
+        let err = api
+            .send::<serde_json::Value, _>("create", Idempotency::NonIdempotent, |c| c.post(&url))
+            .await
+            .unwrap_err();
+
+        assert!(format!("{:#}", err).contains("may already have been applied"));
+        assert_eq!(server.count("/create"), 1);
+
+        // The same failure is retried for an idempotent request
+        let url = format!("{}/read", server.base_url);
+        api.send::<serde_json::Value, _>("read", Idempotency::Idempotent, |c| c.get(&url))
+            .await
+            .unwrap_err();
+        assert_eq!(server.count("/read"), 3);
+    }
+}
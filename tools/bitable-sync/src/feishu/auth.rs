@@ -3,12 +3,14 @@ use serde::{Deserialize, Serialize};
 use std::sync::Arc;
 use tokio::sync::Mutex;
 
-const FEISHU_BASE_URL: &str = "https://open.feishu.cn/open-apis";
-
-#[derive(Debug, Serialize)]
-struct TokenRequest {
-    app_id: String,
-    app_secret: String,
+use crate::config::Config;
+use crate::secret::Secret;
+
+/// No `Debug`: it carries the app secret in plain text
+#[derive(Serialize)]
+struct TokenRequest<'a> {
+    app_id: &'a str,
+    app_secret: &'a str,
 }
 
 #[derive(Debug, Deserialize)]
@@ -25,46 +27,131 @@ struct CachedToken {
     expires_at: chrono::DateTime<chrono::Utc>,
 }
 
+/// Refuse to hand out a token with less than this much validity left
+const EXPIRY_MARGIN_MINUTES: i64 = 5;
+/// Start a background refresh once the token gets this close to expiry.
+/// Feishu only issues a fresh token when the current one has <30 min left.
+const REFRESH_AHEAD_MINUTES: i64 = 25;
+
+impl CachedToken {
+    fn is_usable(&self) -> bool {
+        self.expires_at > chrono::Utc::now() + chrono::Duration::minutes(EXPIRY_MARGIN_MINUTES)
+    }
+
+    fn wants_refresh(&self) -> bool {
+        self.expires_at <= chrono::Utc::now() + chrono::Duration::minutes(REFRESH_AHEAD_MINUTES)
+    }
+}
+
 #[derive(Clone)]
 pub struct FeishuAuth {
+    base_url: String,
     app_id: String,
-    app_secret: String,
+    app_secret: Secret,
     client: reqwest::Client,
     cached: Arc<Mutex<Option<CachedToken>>>,
+    /// Held for the duration of a token request, so concurrent callers
+    /// wait for the in-flight refresh instead of starting their own.
+    refresh_lock: Arc<Mutex<()>>,
 }
 
 impl FeishuAuth {
-    pub fn new(app_id: String, app_secret: String) -> Self {
+    /// `base_url` is the Open API root, e.g. `https://open.feishu.cn/open-apis`
+    pub fn new(base_url: &str, app_id: String, app_secret: Secret) -> Self {
         Self {
+            base_url: base_url.trim_end_matches('/').to_string(),
             app_id,
             app_secret,
             client: reqwest::Client::new(),
             cached: Arc::new(Mutex::new(None)),
+            refresh_lock: Arc::new(Mutex::new(())),
         }
     }
 
+    pub fn from_config(config: &Config) -> Self {
+        Self::new(
+            &config.feishu_base_url,
+            config.feishu_app_id.clone(),
+            config.feishu_app_secret.clone(),
+        )
+    }
+
+    /// Open API root shared by every client built on this auth
+    pub fn base_url(&self) -> &str {
+        &self.base_url
+    }
+
     pub async fn get_token(&self) -> Result<String> {
         // Check cache first
-        {
-            let cached = self.cached.lock().await;
-            if let Some(ref t) = *cached {
-                // Use cached token if it has at least 5 minutes left
-                if t.expires_at > chrono::Utc::now() + chrono::Duration::minutes(5) {
-                    return Ok(t.token.clone());
-                }
+        if let Some(t) = self.cached_token().await {
+            if t.wants_refresh() {
+                self.spawn_background_refresh();
             }
+            return Ok(t.token);
         }
 
-        // Fetch new token
-        let token = self.fetch_token().await?;
+        // Cache is cold or expired: join (or start) the single in-flight refresh
+        let _guard = self.refresh_lock.lock().await;
 
-        Ok(token)
+        // Another caller may have refreshed the token while we waited
+        if let Some(t) = self.cached_token().await {
+            return Ok(t.token);
+        }
+
+        self.fetch_token().await
+    }
+
+    /// Drop the cached token after Feishu rejected it, so the next
+    /// `get_token` fetches a new one. A token that has already been
+    /// replaced by a concurrent refresh is left alone.
+    pub async fn invalidate(&self, rejected: &str) {
+        let mut cached = self.cached.lock().await;
+        if cached.as_ref().is_some_and(|t| t.token == rejected) {
+            tracing::info!("Discarding rejected tenant_access_token");
+            *cached = None;
+        }
+    }
+
+    /// Return the cached token if it is still safe to use
+    async fn cached_token(&self) -> Option<CachedToken> {
+        let cached = self.cached.lock().await;
+        cached.as_ref().filter(|t| t.is_usable()).cloned()
+    }
+
+    /// Refresh the token ahead of expiry without blocking the caller.
+    /// Does nothing if a refresh is already in flight.
+    fn spawn_background_refresh(&self) {
+        let Ok(guard) = self.refresh_lock.clone().try_lock_owned() else {
+            return;
+        };
+
+        let auth = self.clone();
+        tokio::spawn(async move {
+            let _guard = guard;
+
+            // Skip if a refresh completed between the check and the spawn
+            let still_due = auth
+                .cached
+                .lock()
+                .await
+                .as_ref()
+                .map(|t| t.wants_refresh())
+                .unwrap_or(true);
+            if !still_due {
+                return;
+            }
+
+            tracing::debug!("Refreshing tenant_access_token ahead of expiry");
+            if let Err(e) = auth.fetch_token().await {
+                tracing::warn!("Background token refresh failed: {:#}", e);
+            }
+        });
     }
 
     async fn fetch_token(&self) -> Result<String> {
         let url = format!(
             "{}/auth/v3/tenant_access_token/internal",
-            FEISHU_BASE_URL
+            self.base_url
         );
 
         tracing::info!("Fetching new tenant_access_token");
@@ -73,8 +160,8 @@ impl FeishuAuth {
             .client
             .post(&url)
             .json(&TokenRequest {
-                app_id: self.app_id.clone(),
-                app_secret: self.app_secret.clone(),
+                app_id: &self.app_id,
+                app_secret: self.app_secret.expose(),
             })
             .send()
             .await
@@ -103,3 +190,44 @@ impl FeishuAuth {
         Ok(token)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::feishu::stub::{token_response, StubServer};
+
+    #[tokio::test]
+    async fn concurrent_callers_share_one_token_request() {
+        let server = StubServer::start(|_| token_response("t-shared", 7200)).await;
+        let auth = FeishuAuth::new(&server.base_url, "app".into(), "secret".into());
+
+        let (a, b, c) = tokio::join!(auth.get_token(), auth.get_token(), auth.get_token());
+        assert_eq!(a.unwrap(), "t-shared");
+        assert_eq!(b.unwrap(), "t-shared");
+        assert_eq!(c.unwrap(), "t-shared");
+        assert_eq!(server.count("/auth/v3/tenant_access_token"), 1);
+    }
+
+    #[tokio::test]
+    async fn near_expiry_token_is_served_while_refreshing() {
+        let server = StubServer::start(|_| token_response("t-new", 7200)).await;
+        let auth = FeishuAuth::new(&server.base_url, "app".into(), "secret".into());
+        *auth.cached.lock().await = Some(CachedToken {
+            token: "t-old".to_string(),
+            expires_at: chrono::Utc::now() + chrono::Duration::minutes(10),
+        });
+
+        // Still valid, so the caller gets it immediately
+        assert_eq!(auth.get_token().await.unwrap(), "t-old");
+
+        // The background refresh replaces it shortly after
+        for _ in 0..50 {
+            if auth.cached_token().await.map(|t| t.token) == Some("t-new".to_string()) {
+                break;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+        }
+        assert_eq!(auth.get_token().await.unwrap(), "t-new");
+        assert_eq!(server.count("/auth/v3/tenant_access_token"), 1);
+    }
+}
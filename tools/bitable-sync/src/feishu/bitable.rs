@@ -2,43 +2,28 @@ use anyhow::{Context, Result};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 
+use super::api::{ApiError, FeishuApi, Idempotency};
 use super::auth::FeishuAuth;
+use super::search::SearchOptions;
 
-const FEISHU_BASE_URL: &str = "https://open.feishu.cn/open-apis";
 const PAGE_SIZE: i32 = 500;
+/// Max records per batch_create / batch_update / batch_delete call
+const BATCH_LIMIT: usize = 500;
+/// Max record ids per batch_get call
+const BATCH_GET_LIMIT: usize = 100;
 
 // ============================================================
 // Response types
 // ============================================================
 
-#[derive(Debug, Deserialize)]
-struct ApiResponse<T> {
-    code: i32,
-    msg: String,
-    data: Option<T>,
-}
-
-#[derive(Debug, Deserialize)]
-struct ListRecordsData {
-    has_more: bool,
-    page_token: Option<String>,
-    total: Option<i32>,
-    items: Option<Vec<RecordItem>>,
-}
-
-#[derive(Debug, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone)]
 #[allow(dead_code)]
 pub struct RecordItem {
     pub record_id: String,
     pub fields: HashMap<String, serde_json::Value>,
-}
-
-#[derive(Debug, Deserialize)]
-#[allow(dead_code)]
-struct ListTablesData {
-    has_more: Option<bool>,
-    page_token: Option<String>,
-    items: Option<Vec<TableInfo>>,
+    /// Milliseconds since epoch; only returned when `automatic_fields` is requested
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_modified_time: Option<i64>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -49,6 +34,14 @@ pub struct TableInfo {
     pub revision: Option<i64>,
 }
 
+#[derive(Debug, Deserialize)]
+#[allow(dead_code)]
+pub struct ViewInfo {
+    pub view_id: String,
+    pub view_name: String,
+    pub view_type: Option<String>,
+}
+
 #[derive(Debug, Deserialize)]
 #[allow(dead_code)]
 struct CreateTableData {
@@ -57,13 +50,41 @@ struct CreateTableData {
     field_id_list: Option<Vec<String>>,
 }
 
+#[derive(Debug, Deserialize)]
+struct BatchRecordsData {
+    records: Option<Vec<RecordItem>>,
+}
+
+/// One record to change in `batch_update_records`
+#[derive(Debug, Clone, Serialize)]
+pub struct RecordUpdate {
+    pub record_id: String,
+    pub fields: HashMap<String, serde_json::Value>,
+}
+
+/// A record that could not be written, keyed by its upsert key
+/// (or its position in the input when the key is missing)
+#[derive(Debug, Clone)]
+pub struct RecordFailure {
+    pub key: String,
+    pub error: String,
+}
+
+/// Result of `upsert_records`
+#[derive(Debug, Default)]
+pub struct UpsertReport {
+    pub created: usize,
+    pub updated: usize,
+    pub failed: Vec<RecordFailure>,
+}
+
 #[derive(Debug, Deserialize)]
 #[allow(dead_code)]
 struct FieldData {
     field: FieldInfo,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 #[allow(dead_code)]
 pub struct FieldInfo {
     pub field_id: String,
@@ -75,13 +96,6 @@ pub struct FieldInfo {
     pub property: Option<serde_json::Value>,
 }
 
-#[derive(Debug, Deserialize)]
-#[allow(dead_code)]
-struct ListFieldsData {
-    has_more: bool,
-    items: Option<Vec<FieldInfo>>,
-}
-
 #[derive(Debug, Deserialize)]
 #[allow(dead_code)]
 struct WikiNodeData {
@@ -102,7 +116,7 @@ pub struct WikiNode {
 // ============================================================
 
 /// Feishu Bitable field types
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 #[allow(dead_code)]
 pub enum FieldType {
     /// 多行文本 (type=1)
@@ -127,8 +141,49 @@ pub enum FieldType {
     Attachment = 17,
     /// 单向关联 (type=18)
     SingleLink = 18,
+    /// 查找引用 (type=19)
+    Lookup = 19,
+    /// 公式 (type=20)
+    Formula = 20,
     /// 双向关联 (type=21)
     DuplexLink = 21,
+    /// 创建时间 (type=1001)
+    CreatedTime = 1001,
+    /// 最后更新时间 (type=1002)
+    ModifiedTime = 1002,
+}
+
+impl FieldType {
+    const ALL: [FieldType; 16] = [
+        FieldType::Text,
+        FieldType::Number,
+        FieldType::SingleSelect,
+        FieldType::MultiSelect,
+        FieldType::DateTime,
+        FieldType::Checkbox,
+        FieldType::Person,
+        FieldType::Phone,
+        FieldType::Url,
+        FieldType::Attachment,
+        FieldType::SingleLink,
+        FieldType::Lookup,
+        FieldType::Formula,
+        FieldType::DuplexLink,
+        FieldType::CreatedTime,
+        FieldType::ModifiedTime,
+    ];
+
+    pub fn from_code(code: i32) -> Option<Self> {
+        Self::ALL.into_iter().find(|t| *t as i32 == code)
+    }
+
+    /// Readable name for reports, e.g. "Number" or "type 22" for unknown codes
+    pub fn describe(code: i32) -> String {
+        match Self::from_code(code) {
+            Some(t) => format!("{:?}", t),
+            None => format!("type {}", code),
+        }
+    }
 }
 
 /// Field definition for creating a table or adding a field
@@ -201,6 +256,88 @@ impl FieldDef {
         }
     }
 
+    /// Create a multi select field with options
+    pub fn multi_select(name: &str, options: &[&str]) -> Self {
+        Self {
+            field_type: FieldType::MultiSelect as i32,
+            ..Self::single_select(name, options)
+        }
+    }
+
+    /// Create a date field
+    /// date_formatter: e.g. "yyyy/MM/dd" or "yyyy/MM/dd HH:mm"
+    pub fn datetime(name: &str, date_formatter: &str) -> Self {
+        Self {
+            field_name: name.to_string(),
+            field_type: FieldType::DateTime as i32,
+            property: Some(serde_json::json!({
+                "date_formatter": date_formatter,
+                "auto_fill": false,
+            })),
+        }
+    }
+
+    /// Create a person field
+    pub fn person(name: &str, multiple: bool) -> Self {
+        Self {
+            field_name: name.to_string(),
+            field_type: FieldType::Person as i32,
+            property: Some(serde_json::json!({"multiple": multiple})),
+        }
+    }
+
+    /// Create a hyperlink field
+    pub fn url(name: &str) -> Self {
+        Self {
+            field_name: name.to_string(),
+            field_type: FieldType::Url as i32,
+            property: None,
+        }
+    }
+
+    /// Create a formula field, e.g. `([零售价]-[成本价])/[零售价]` with formatter "0.00%"
+    pub fn formula(name: &str, expression: &str, formatter: &str) -> Self {
+        Self {
+            field_name: name.to_string(),
+            field_type: FieldType::Formula as i32,
+            property: Some(serde_json::json!({
+                "formula_expression": expression,
+                "formatter": formatter,
+            })),
+        }
+    }
+
+    /// Create a lookup field that pulls `target_field_id` from the records
+    /// linked in `target_table_id` (e.g. a brand's 产地 through the 品牌 link)
+    pub fn lookup(name: &str, target_table_id: &str, target_field_id: &str) -> Self {
+        Self {
+            field_name: name.to_string(),
+            field_type: FieldType::Lookup as i32,
+            property: Some(serde_json::json!({
+                "target_field": target_field_id,
+                "filter_info": {"target_table": target_table_id},
+            })),
+        }
+    }
+
+    /// Create a created-time field (filled in by Feishu)
+    pub fn created_time(name: &str) -> Self {
+        Self {
+            field_name: name.to_string(),
+            field_type: FieldType::CreatedTime as i32,
+            property: Some(serde_json::json!({"date_formatter": "yyyy/MM/dd HH:mm"})),
+        }
+    }
+
+    /// Create a last-modified-time field (filled in by Feishu)
+    pub fn modified_time(name: &str) -> Self {
+        Self {
+            field_name: name.to_string(),
+            field_type: FieldType::ModifiedTime as i32,
+            property: Some(serde_json::json!({"date_formatter": "yyyy/MM/dd HH:mm"})),
+        }
+    }
+
     /// Create a single-direction link field (关联)
     pub fn link(name: &str, linked_table_id: &str) -> Self {
         Self {
@@ -216,16 +353,14 @@ impl FieldDef {
 // ============================================================
 
 pub struct BitableClient {
-    auth: FeishuAuth,
-    client: reqwest::Client,
+    api: FeishuApi,
     app_token: String,
 }
 
 impl BitableClient {
     pub fn new(auth: FeishuAuth, app_token: String) -> Self {
         Self {
-            auth,
-            client: reqwest::Client::new(),
+            api: FeishuApi::new(auth),
             app_token,
         }
     }
@@ -233,128 +368,143 @@ impl BitableClient {
     fn tables_url(&self) -> String {
         format!(
             "{}/bitable/v1/apps/{}/tables",
-            FEISHU_BASE_URL, self.app_token
+            self.api.base_url(),
+            self.app_token
         )
     }
 
     // ---- Read operations ----
 
-    /// List all tables in the bitable app
+    /// List all tables in the bitable app (handles pagination)
     pub async fn list_tables(&self) -> Result<Vec<TableInfo>> {
-        let token = self.auth.get_token().await?;
-        let resp = self
-            .client
-            .get(&self.tables_url())
-            .header("Authorization", format!("Bearer {}", token))
-            .send()
+        let url = self.tables_url();
+        self.api
+            .paginate("list tables", |c| c.get(&url).query(&[("page_size", 100)]))
             .await
-            .context("Failed to list tables")?
-            .json::<ApiResponse<ListTablesData>>()
-            .await
-            .context("Failed to parse tables response")?;
-
-        if resp.code != 0 {
-            anyhow::bail!("Failed to list tables: {} - {}", resp.code, resp.msg);
-        }
-
-        Ok(resp
-            .data
-            .and_then(|d| d.items)
-            .unwrap_or_default())
     }
 
-    /// List all fields in a table
-    #[allow(dead_code)]
+    /// List all fields in a table (handles pagination)
     pub async fn list_fields(&self, table_id: &str) -> Result<Vec<FieldInfo>> {
-        let token = self.auth.get_token().await?;
         let url = format!("{}/{}/fields", self.tables_url(), table_id);
-
-        let resp = self
-            .client
-            .get(&url)
-            .header("Authorization", format!("Bearer {}", token))
-            .send()
+        self.api
+            .paginate(&format!("list fields of table {}", table_id), |c| {
+                c.get(&url).query(&[("page_size", 100)])
+            })
             .await
-            .context("Failed to list fields")?
-            .json::<ApiResponse<ListFieldsData>>()
-            .await
-            .context("Failed to parse fields response")?;
-
-        if resp.code != 0 {
-            anyhow::bail!("Failed to list fields: {} - {}", resp.code, resp.msg);
-        }
-
-        Ok(resp
-            .data
-            .and_then(|d| d.items)
-            .unwrap_or_default())
     }
 
-    /// Read all records from a table (handles pagination)
-    pub async fn read_all_records(&self, table_id: &str) -> Result<Vec<RecordItem>> {
-        let mut all_records = Vec::new();
-        let mut page_token: Option<String> = None;
+    /// List all views of a table (handles pagination)
+    pub async fn list_views(&self, table_id: &str) -> Result<Vec<ViewInfo>> {
+        let url = format!("{}/{}/views", self.tables_url(), table_id);
+        self.api
+            .paginate(&format!("list views of table {}", table_id), |c| {
+                c.get(&url).query(&[("page_size", 100)])
+            })
+            .await
+    }
 
-        loop {
-            let token = self.auth.get_token().await?;
-            let mut url = format!(
-                "{}/{}/records?page_size={}",
-                self.tables_url(),
-                table_id,
-                PAGE_SIZE
-            );
+    /// Turn a configured view (id `vewXXX` or view name) into a view_id
+    pub async fn resolve_view_id(&self, table_id: &str, view: &str) -> Result<String> {
+        if view.starts_with("vew") {
+            return Ok(view.to_string());
+        }
 
-            if let Some(ref pt) = page_token {
-                url.push_str(&format!("&page_token={}", pt));
+        let views = self.list_views(table_id).await?;
+        match views.iter().find(|v| v.view_name == view) {
+            Some(v) => {
+                tracing::debug!("View '{}' in table {} -> {}", view, table_id, v.view_id);
+                Ok(v.view_id.clone())
             }
-
-            tracing::debug!("Fetching records from {}", table_id);
-
-            let resp = self
-                .client
-                .get(&url)
-                .header("Authorization", format!("Bearer {}", token))
-                .send()
-                .await
-                .with_context(|| format!("Failed to read records from table {}", table_id))?
-                .json::<ApiResponse<ListRecordsData>>()
-                .await
-                .with_context(|| format!("Failed to parse records from table {}", table_id))?;
-
-            if resp.code != 0 {
+            None => {
+                let names: Vec<&str> = views.iter().map(|v| v.view_name.as_str()).collect();
                 anyhow::bail!(
-                    "Failed to read records from {}: {} - {}",
+                    "View '{}' not found in table {} (available: {})",
+                    view,
                     table_id,
-                    resp.code,
-                    resp.msg
-                );
+                    names.join(", ")
+                )
             }
+        }
+    }
 
-            if let Some(data) = resp.data {
-                if let Some(total) = data.total {
-                    tracing::info!("Table {} has {} total records", table_id, total);
+    /// Read all records from a table (handles pagination).
+    /// With a `view_id`, only that view's records are returned, in its order.
+    pub async fn read_all_records(
+        &self,
+        table_id: &str,
+        view_id: Option<&str>,
+    ) -> Result<Vec<RecordItem>> {
+        let url = format!("{}/{}/records", self.tables_url(), table_id);
+        tracing::debug!("Fetching records from {}", table_id);
+
+        let records: Vec<RecordItem> = self
+            .api
+            .paginate(&format!("read records from table {}", table_id), |c| {
+                let req = c.get(&url).query(&[("page_size", PAGE_SIZE)]);
+                match view_id {
+                    Some(view_id) => req.query(&[("view_id", view_id)]),
+                    None => req,
                 }
+            })
+            .await?;
 
-                if let Some(items) = data.items {
-                    all_records.extend(items);
-                }
+        tracing::info!("Read {} records from table {}", records.len(), table_id);
+        Ok(records)
+    }
 
-                if data.has_more {
-                    page_token = data.page_token;
-                } else {
-                    break;
-                }
-            } else {
-                break;
-            }
-        }
+    /// Search records server-side with optional view, filter, sort and
+    /// field projection (handles pagination)
+    pub async fn search_records(
+        &self,
+        table_id: &str,
+        options: &SearchOptions,
+    ) -> Result<Vec<RecordItem>> {
+        let url = format!("{}/{}/records/search", self.tables_url(), table_id);
+
+        let records: Vec<RecordItem> = self
+            .api
+            .paginate(&format!("search records in table {}", table_id), |c| {
+                c.post(&url)
+                    .query(&[("page_size", PAGE_SIZE)])
+                    .json(options)
+            })
+            .await?;
 
         tracing::info!(
-            "Read {} records from table {}",
-            all_records.len(),
+            "Search returned {} records from table {}",
+            records.len(),
             table_id
         );
-        Ok(all_records)
+        Ok(records)
+    }
+
+    /// Fetch specific records by id, including their modification time.
+    /// Ids that no longer exist are silently left out.
+    pub async fn batch_get_records(
+        &self,
+        table_id: &str,
+        record_ids: &[String],
+    ) -> Result<Vec<RecordItem>> {
+        let url = format!("{}/{}/records/batch_get", self.tables_url(), table_id);
+        let mut records = Vec::with_capacity(record_ids.len());
+
+        for chunk in record_ids.chunks(BATCH_GET_LIMIT) {
+            let body = serde_json::json!({"record_ids": chunk, "automatic_fields": true});
+            let data = self
+                .api
+                .send::<BatchRecordsData, _>(
+                    &format!("get {} records from {}", chunk.len(), table_id),
+                    Idempotency::Idempotent,
+                    |c| c.post(&url).json(&body),
+                )
+                .await?;
+            if let Some(records_in_chunk) = data.and_then(|d| d.records) {
+                records.extend(records_in_chunk);
+            }
+        }
+
+        tracing::info!("Fetched {} records from table {}", records.len(), table_id);
+        Ok(records)
     }
 
     // ---- Write operations: Table management ----
@@ -367,7 +517,7 @@ impl BitableClient {
         view_name: &str,
         fields: &[FieldDef],
     ) -> Result<String> {
-        let token = self.auth.get_token().await?;
+        let url = self.tables_url();
         let body = serde_json::json!({
             "table": {
                 "name": name,
@@ -376,24 +526,14 @@ impl BitableClient {
             }
         });
 
-        let resp = self
-            .client
-            .post(&self.tables_url())
-            .header("Authorization", format!("Bearer {}", token))
-            .json(&body)
-            .send()
-            .await
-            .with_context(|| format!("Failed to create table '{}'", name))?
-            .json::<ApiResponse<CreateTableData>>()
-            .await
-            .with_context(|| format!("Failed to parse create table response for '{}'", name))?;
-
-        if resp.code != 0 {
-            anyhow::bail!("Failed to create table '{}': {} - {}", name, resp.code, resp.msg);
-        }
-
-        let table_id = resp
-            .data
+        let table_id = self
+            .api
+            .send::<CreateTableData, _>(
+                &format!("create table '{}'", name),
+                Idempotency::NonIdempotent,
+                |c| c.post(&url).json(&body),
+            )
+            .await?
             .context("No data in create table response")?
             .table_id;
 
@@ -403,64 +543,229 @@ impl BitableClient {
 
     /// Delete a table
     pub async fn delete_table(&self, table_id: &str) -> Result<()> {
-        let token = self.auth.get_token().await?;
         let url = format!("{}/{}", self.tables_url(), table_id);
 
-        let resp = self
-            .client
-            .delete(&url)
-            .header("Authorization", format!("Bearer {}", token))
-            .send()
-            .await
-            .with_context(|| format!("Failed to delete table {}", table_id))?
-            .json::<ApiResponse<serde_json::Value>>()
+        self.api
+            .send::<serde_json::Value, _>(
+                &format!("delete table {}", table_id),
+                Idempotency::NonIdempotent,
+                |c| c.delete(&url),
+            )
             .await?;
 
-        if resp.code != 0 {
-            anyhow::bail!("Failed to delete table {}: {} - {}", table_id, resp.code, resp.msg);
-        }
-
         tracing::info!("Deleted table {}", table_id);
         Ok(())
     }
 
     // ---- Write operations: Record management ----
 
-    /// Batch create records in a table
-    #[allow(dead_code)]
+    /// Batch create records in a table.
+    /// Each record is `{"fields": {...}}`; large inputs are split into chunks.
+    /// Returns the created records (with their new record_ids) in input order.
     pub async fn batch_create_records(
         &self,
         table_id: &str,
         records: &[serde_json::Value],
-    ) -> Result<()> {
-        let token = self.auth.get_token().await?;
+    ) -> Result<Vec<RecordItem>> {
+        let mut created = Vec::with_capacity(records.len());
+        for chunk in records.chunks(BATCH_LIMIT) {
+            created.extend(self.batch_create_chunk(table_id, chunk).await?);
+        }
+
+        tracing::info!("Created {} records in table {}", records.len(), table_id);
+        Ok(created)
+    }
+
+    async fn batch_create_chunk(
+        &self,
+        table_id: &str,
+        records: &[serde_json::Value],
+    ) -> Result<Vec<RecordItem>> {
         let url = format!("{}/{}/records/batch_create", self.tables_url(), table_id);
         let body = serde_json::json!({"records": records});
+        // One token for every attempt, so Feishu drops a replayed create
+        let token = client_token();
+
+        let data = self
+            .api
+            .send::<BatchRecordsData, _>(
+                &format!("batch create {} records in {}", records.len(), table_id),
+                Idempotency::Idempotent,
+                |c| c.post(&url).query(&[("client_token", &token)]).json(&body),
+            )
+            .await?;
 
-        let resp = self
-            .client
-            .post(&url)
-            .header("Authorization", format!("Bearer {}", token))
-            .json(&body)
-            .send()
-            .await
-            .with_context(|| format!("Failed to batch create records in {}", table_id))?
-            .json::<ApiResponse<serde_json::Value>>()
+        Ok(data.and_then(|d| d.records).unwrap_or_default())
+    }
+
+    /// Batch update records in a table (split into chunks)
+    pub async fn batch_update_records(
+        &self,
+        table_id: &str,
+        updates: &[RecordUpdate],
+    ) -> Result<()> {
+        for chunk in updates.chunks(BATCH_LIMIT) {
+            self.batch_update_chunk(table_id, chunk).await?;
+        }
+
+        tracing::info!("Updated {} records in table {}", updates.len(), table_id);
+        Ok(())
+    }
+
+    async fn batch_update_chunk(&self, table_id: &str, updates: &[RecordUpdate]) -> Result<()> {
+        let url = format!("{}/{}/records/batch_update", self.tables_url(), table_id);
+        let body = serde_json::json!({"records": updates});
+
+        self.api
+            .send::<serde_json::Value, _>(
+                &format!("batch update {} records in {}", updates.len(), table_id),
+                Idempotency::Idempotent,
+                |c| c.post(&url).json(&body),
+            )
             .await?;
+        Ok(())
+    }
 
-        if resp.code != 0 {
-            anyhow::bail!(
-                "Failed to batch create records in {}: {} - {}",
-                table_id,
-                resp.code,
-                resp.msg
-            );
+    /// Batch delete records by record_id (split into chunks)
+    #[allow(dead_code)]
+    pub async fn batch_delete_records(&self, table_id: &str, record_ids: &[String]) -> Result<()> {
+        let url = format!("{}/{}/records/batch_delete", self.tables_url(), table_id);
+
+        for chunk in record_ids.chunks(BATCH_LIMIT) {
+            let body = serde_json::json!({"records": chunk});
+            self.api
+                .send::<serde_json::Value, _>(
+                    &format!("batch delete {} records in {}", chunk.len(), table_id),
+                    Idempotency::NonIdempotent,
+                    |c| c.post(&url).json(&body),
+                )
+                .await?;
         }
 
-        tracing::info!("Created {} records in table {}", records.len(), table_id);
+        tracing::info!("Deleted {} records in table {}", record_ids.len(), table_id);
         Ok(())
     }
 
+    /// Create or update records, matching existing rows on `key_field` (e.g. "商品ID").
+    ///
+    /// Each input is a field map that must contain `key_field`. Rows whose key
+    /// already exists in the table are updated, the rest are created. A chunk
+    /// Feishu rejects is halved and resent until the offending records are
+    /// isolated, so only those are reported as failed.
+    pub async fn upsert_records(
+        &self,
+        table_id: &str,
+        key_field: &str,
+        records: &[HashMap<String, serde_json::Value>],
+    ) -> Result<UpsertReport> {
+        use crate::models::bitable_value::RecordFields;
+
+        let mut existing: HashMap<String, String> = HashMap::new();
+        for item in self.read_all_records(table_id, None).await? {
+            let key = match RecordFields::new(&item.fields).text(key_field) {
+                Ok(Some(key)) => key,
+                Ok(None) => continue,
+                Err(e) => {
+                    tracing::warn!("Skipping record {} in table {}: {}", item.record_id, table_id, e);
+                    continue;
+                }
+            };
+            if existing.contains_key(&key) {
+                tracing::warn!(
+                    "Table {} has duplicate {} '{}', updating the first match only",
+                    table_id,
+                    key_field,
+                    key
+                );
+                continue;
+            }
+            existing.insert(key, item.record_id);
+        }
+
+        let mut report = UpsertReport::default();
+        let mut seen = std::collections::HashSet::new();
+        let mut to_create: Vec<(String, serde_json::Value)> = Vec::new();
+        let mut to_update: Vec<(String, RecordUpdate)> = Vec::new();
+
+        for (i, fields) in records.iter().enumerate() {
+            let key = match RecordFields::new(fields).text(key_field) {
+                Ok(Some(key)) => key,
+                Ok(None) => {
+                    report.failed.push(RecordFailure {
+                        key: format!("#{}", i),
+                        error: format!("missing key field '{}'", key_field),
+                    });
+                    continue;
+                }
+                Err(e) => {
+                    report.failed.push(RecordFailure {
+                        key: format!("#{}", i),
+                        error: e.to_string(),
+                    });
+                    continue;
+                }
+            };
+            if !seen.insert(key.clone()) {
+                report.failed.push(RecordFailure {
+                    key,
+                    error: "duplicate key in input".to_string(),
+                });
+                continue;
+            }
+            match existing.get(&key) {
+                Some(record_id) => to_update.push((
+                    key,
+                    RecordUpdate {
+                        record_id: record_id.clone(),
+                        fields: fields.clone(),
+                    },
+                )),
+                None => to_create.push((key, serde_json::json!({"fields": fields}))),
+            }
+        }
+
+        // Chunks still to send, last one first
+        let mut pending: Vec<&[(String, RecordUpdate)]> =
+            to_update.chunks(BATCH_LIMIT).rev().collect();
+        while let Some(chunk) = pending.pop() {
+            let updates: Vec<RecordUpdate> = chunk.iter().map(|(_, u)| u.clone()).collect();
+            match self.batch_update_chunk(table_id, &updates).await {
+                Ok(()) => report.updated += chunk.len(),
+                Err(e) if chunk.len() > 1 && is_record_rejection(&e) => {
+                    let (first, second) = chunk.split_at(chunk.len() / 2);
+                    pending.extend([second, first]);
+                }
+                Err(e) => fail_chunk(&mut report, chunk.iter().map(|(k, _)| k), &e),
+            }
+        }
+
+        let mut pending: Vec<&[(String, serde_json::Value)]> =
+            to_create.chunks(BATCH_LIMIT).rev().collect();
+        while let Some(chunk) = pending.pop() {
+            let creates: Vec<serde_json::Value> = chunk.iter().map(|(_, r)| r.clone()).collect();
+            match self.batch_create_chunk(table_id, &creates).await {
+                Ok(_) => report.created += chunk.len(),
+                Err(e) if chunk.len() > 1 && is_record_rejection(&e) => {
+                    let (first, second) = chunk.split_at(chunk.len() / 2);
+                    pending.extend([second, first]);
+                }
+                Err(e) => fail_chunk(&mut report, chunk.iter().map(|(k, _)| k), &e),
+            }
+        }
+
+        tracing::info!(
+            "Upserted into table {}: {} created, {} updated, {} failed",
+            table_id,
+            report.created,
+            report.updated,
+            report.failed.len()
+        );
+        for f in &report.failed {
+            tracing::warn!("Upsert failed for {} '{}': {}", key_field, f.key, f.error);
+        }
+        Ok(report)
+    }
+
     // ---- Write operations: Field management ----
 
     /// Add a field to a table
@@ -469,30 +774,21 @@ impl BitableClient {
         table_id: &str,
         field: &FieldDef,
     ) -> Result<String> {
-        let token = self.auth.get_token().await?;
         let url = format!("{}/{}/fields", self.tables_url(), table_id);
+        let token = client_token();
+
+        let field_id = self
+            .api
+            .send::<FieldData, _>(
+                &format!("create field '{}' in table {}", field.field_name, table_id),
+                Idempotency::Idempotent,
+                |c| c.post(&url).query(&[("client_token", &token)]).json(field),
+            )
+            .await?
+            .context("No data")?
+            .field
+            .field_id;
 
-        let resp = self
-            .client
-            .post(&url)
-            .header("Authorization", format!("Bearer {}", token))
-            .json(field)
-            .send()
-            .await
-            .with_context(|| format!("Failed to create field '{}'", field.field_name))?
-            .json::<ApiResponse<FieldData>>()
-            .await?;
-
-        if resp.code != 0 {
-            anyhow::bail!(
-                "Failed to create field '{}': {} - {}",
-                field.field_name,
-                resp.code,
-                resp.msg
-            );
-        }
-
-        let field_id = resp.data.context("No data")?.field.field_id;
         tracing::info!("Created field '{}' -> {}", field.field_name, field_id);
         Ok(field_id)
     }
@@ -506,29 +802,16 @@ impl BitableClient {
         field_id: &str,
         field: &FieldDef,
     ) -> Result<()> {
-        let token = self.auth.get_token().await?;
         let url = format!("{}/{}/fields/{}", self.tables_url(), table_id, field_id);
 
-        let resp = self
-            .client
-            .put(&url)
-            .header("Authorization", format!("Bearer {}", token))
-            .json(field)
-            .send()
-            .await
-            .with_context(|| format!("Failed to update field {}", field_id))?
-            .json::<ApiResponse<serde_json::Value>>()
+        self.api
+            .send::<serde_json::Value, _>(
+                &format!("update field {} in table {}", field_id, table_id),
+                Idempotency::Idempotent,
+                |c| c.put(&url).json(field),
+            )
             .await?;
 
-        if resp.code != 0 {
-            anyhow::bail!(
-                "Failed to update field {}: {} - {}",
-                field_id,
-                resp.code,
-                resp.msg
-            );
-        }
-
         tracing::info!("Updated field {} -> '{}'", field_id, field.field_name);
         Ok(())
     }
@@ -536,23 +819,16 @@ impl BitableClient {
     /// Delete a field from a table
     #[allow(dead_code)]
     pub async fn delete_field(&self, table_id: &str, field_id: &str) -> Result<()> {
-        let token = self.auth.get_token().await?;
         let url = format!("{}/{}/fields/{}", self.tables_url(), table_id, field_id);
 
-        let resp = self
-            .client
-            .delete(&url)
-            .header("Authorization", format!("Bearer {}", token))
-            .send()
-            .await
-            .with_context(|| format!("Failed to delete field {}", field_id))?
-            .json::<ApiResponse<serde_json::Value>>()
+        self.api
+            .send::<serde_json::Value, _>(
+                &format!("delete field {} in table {}", field_id, table_id),
+                Idempotency::NonIdempotent,
+                |c| c.delete(&url),
+            )
             .await?;
 
-        if resp.code != 0 {
-            anyhow::bail!("Failed to delete field {}: {} - {}", field_id, resp.code, resp.msg);
-        }
-
         tracing::info!("Deleted field {}", field_id);
         Ok(())
     }
@@ -561,35 +837,20 @@ impl BitableClient {
 
     /// Resolve a wiki node token to get the actual bitable app_token
     /// Wiki-embedded bitables have a different URL format; this extracts the obj_token
-    #[allow(dead_code)]
     pub async fn resolve_wiki_node(auth: &FeishuAuth, wiki_token: &str) -> Result<WikiNode> {
-        let token = auth.get_token().await?;
-        let client = reqwest::Client::new();
-        let url = format!(
-            "{}/wiki/v2/spaces/get_node?token={}",
-            FEISHU_BASE_URL, wiki_token
-        );
+        let api = FeishuApi::new(auth.clone());
+        let url = format!("{}/wiki/v2/spaces/get_node", api.base_url());
+
+        let node = api
+            .send::<WikiNodeData, _>(
+                &format!("resolve wiki node '{}'", wiki_token),
+                Idempotency::Idempotent,
+                |c| c.get(&url).query(&[("token", wiki_token)]),
+            )
+            .await?
+            .context("No data in wiki response")?
+            .node;
 
-        let resp = client
-            .get(&url)
-            .header("Authorization", format!("Bearer {}", token))
-            .send()
-            .await
-            .context("Failed to resolve wiki node")?
-            .json::<ApiResponse<WikiNodeData>>()
-            .await
-            .context("Failed to parse wiki node response")?;
-
-        if resp.code != 0 {
-            anyhow::bail!(
-                "Failed to resolve wiki node '{}': {} - {}",
-                wiki_token,
-                resp.code,
-                resp.msg
-            );
-        }
-
-        let node = resp.data.context("No data in wiki response")?.node;
         tracing::info!(
             "Wiki node '{}' -> obj_token={}, obj_type={}, title={}",
             wiki_token,
@@ -600,3 +861,226 @@ impl BitableClient {
         Ok(node)
     }
 }
+
+/// A random uuid for the `client_token` of a create, so Feishu can tell a
+/// replayed request from a new one
+fn client_token() -> String {
+    let mut bytes: [u8; 16] = fastrand::u128(..).to_be_bytes();
+    bytes[6] = (bytes[6] & 0x0f) | 0x40;
+    bytes[8] = (bytes[8] & 0x3f) | 0x80;
+    let hex: String = bytes.iter().map(|b| format!("{:02x}", b)).collect();
+    format!(
+        "{}-{}-{}-{}-{}",
+        &hex[..8],
+        &hex[8..12],
+        &hex[12..16],
+        &hex[16..20],
+        &hex[20..]
+    )
+}
+
+/// Only a chunk refused over a record's cells is worth splitting: a
+/// table-level failure (permissions, missing table) fails every half too,
+/// and a write that failed after retries may have been applied, so halves
+/// of it with new client tokens could duplicate records
+fn is_record_rejection(error: &anyhow::Error) -> bool {
+    error
+        .downcast_ref::<ApiError>()
+        .is_some_and(ApiError::is_record_rejection)
+}
+
+fn fail_chunk<'a>(
+    report: &mut UpsertReport,
+    keys: impl Iterator<Item = &'a String>,
+    error: &anyhow::Error,
+) {
+    for key in keys {
+        report.failed.push(RecordFailure {
+            key: key.clone(),
+            error: format!("{:#}", error),
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::feishu::stub::{token_response, StubResponse, StubServer};
+
+    fn fields(pairs: &[(&str, &str)]) -> HashMap<String, serde_json::Value> {
+        pairs
+            .iter()
+            .map(|(k, v)| (k.to_string(), serde_json::json!(v)))
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn upsert_updates_matches_and_creates_the_rest() {
+        let server = StubServer::start(|req| {
+            if req.path.starts_with("/auth/") {
+                return token_response("t", 7200);
+            }
+            if req.method == "GET" && req.path.contains("/records") {
+                return StubResponse::json(serde_json::json!({"code": 0, "msg": "ok", "data": {
+                    "has_more": false,
+                    "items": [{
+                        "record_id": "rec1",
+                        "fields": {"商品ID": [{"type": "text", "text": "P001"}]}
+                    }]
+                }}));
+            }
+            StubResponse::json(serde_json::json!({"code": 0, "msg": "ok", "data": {"records": []}}))
+        })
+        .await;
+
+        let auth = FeishuAuth::new(&server.base_url, "app".into(), "secret".into());
+        let client = BitableClient::new(auth, "app_token".into());
+        let report = client
+            .upsert_records(
+                "tbl1",
+                "商品ID",
+                &[
+                    fields(&[("商品ID", "P001"), ("商品名称", "花雕")]),
+                    fields(&[("商品ID", "P002"), ("商品名称", "加饭")]),
+                    fields(&[("商品名称", "no key")]),
+                    fields(&[("商品ID", "P002"), ("商品名称", "dup")]),
+                ],
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(report.updated, 1);
+        assert_eq!(report.created, 1);
+        assert_eq!(report.failed.len(), 2);
+
+        let requests = server.requests();
+        let update = requests
+            .iter()
+            .find(|r| r.path.ends_with("/records/batch_update"))
+            .unwrap();
+        assert!(update.body.contains("rec1"));
+        let create = requests
+            .iter()
+            .find(|r| r.path.contains("/records/batch_create"))
+            .unwrap();
+        assert!(create.body.contains("P002") && !create.body.contains("dup"));
+    }
+
+    #[tokio::test]
+    async fn rejected_chunk_is_split_to_find_the_bad_record() {
+        let server = StubServer::start(|req| {
+            if req.path.starts_with("/auth/") {
+                return token_response("t", 7200);
+            }
+            if req.method == "GET" {
+                return StubResponse::json(serde_json::json!({"code": 0, "msg": "ok", "data": {
+                    "has_more": false, "items": []
+                }}));
+            }
+            if req.body.contains("bad price") {
+                return StubResponse::json(
+                    serde_json::json!({"code": 1254061, "msg": "NumberFieldConvFail"}),
+                );
+            }
+            StubResponse::json(serde_json::json!({"code": 0, "msg": "ok", "data": {"records": []}}))
+        })
+        .await;
+
+        let auth = FeishuAuth::new(&server.base_url, "app".into(), "secret".into());
+        let client = BitableClient::new(auth, "app_token".into());
+        let records: Vec<_> = (1..=5)
+            .map(|i| {
+                let price = if i == 4 { "bad price" } else { "10" };
+                fields(&[("商品ID", &format!("P00{}", i)), ("零售价", price)])
+            })
+            .collect();
+        let report = client.upsert_records("tbl1", "商品ID", &records).await.unwrap();
+
+        assert_eq!(report.created, 4);
+        assert_eq!(report.failed.len(), 1);
+        assert_eq!(report.failed[0].key, "P004");
+        assert!(report.failed[0].error.contains("1254061"));
+    }
+
+    #[tokio::test]
+    async fn table_level_error_fails_the_chunk_without_splitting() {
+        let server = StubServer::start(|req| {
+            if req.path.starts_with("/auth/") {
+                return token_response("t", 7200);
+            }
+            if req.method == "GET" {
+                return StubResponse::json(serde_json::json!({"code": 0, "msg": "ok", "data": {
+                    "has_more": false, "items": []
+                }}));
+            }
+            StubResponse::json(serde_json::json!({"code": 1254302, "msg": "Permission denied"}))
+                .status(403)
+        })
+        .await;
+
+        let auth = FeishuAuth::new(&server.base_url, "app".into(), "secret".into());
+        let client = BitableClient::new(auth, "app_token".into());
+        let records: Vec<_> = (1..=5)
+            .map(|i| fields(&[("商品ID", &format!("P00{}", i))]))
+            .collect();
+        let report = client.upsert_records("tbl1", "商品ID", &records).await.unwrap();
+
+        assert_eq!(report.failed.len(), 5);
+        assert_eq!(server.count("/bitable/v1/apps/app_token/tables/tbl1/records/batch_create"), 1);
+    }
+
+    #[tokio::test]
+    async fn replayed_create_reuses_its_client_token() {
+        let calls = std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let counter = calls.clone();
+        let server = StubServer::start(move |req| {
+            if req.path.starts_with("/auth/") {
+                return token_response("t", 7200);
+            }
+            // The first create fails after (perhaps) being applied
+            if counter.fetch_add(1, std::sync::atomic::Ordering::SeqCst) == 0 {
+                return StubResponse::json(serde_json::json!({"code": 0, "msg": "boom"})).status(500);
+            }
+            StubResponse::json(serde_json::json!({"code": 0, "msg": "ok", "data": {
+                "records": [{"record_id": "rec9", "fields": {}}]
+            }}))
+        })
+        .await;
+
+        let auth = FeishuAuth::new(&server.base_url, "app".into(), "secret".into());
+        let client = BitableClient::new(auth, "app_token".into());
+        let created = client
+            .batch_create_records("tbl1", &[serde_json::json!({"fields": {"商品ID": "P009"}})])
+            .await
+            .unwrap();
+        assert_eq!(created.len(), 1);
+
+        let tokens: Vec<String> = server
+            .requests()
+            .iter()
+            .filter_map(|r| r.path.split("client_token=").nth(1).map(str::to_string))
+            .collect();
+        assert_eq!(tokens.len(), 2);
+        assert_eq!(tokens[0], tokens[1]);
+        assert_eq!(tokens[0].len(), 36);
+    }
+
+    #[test]
+    fn computed_field_defs_serialize_with_type_codes() {
+        let formula = serde_json::to_value(FieldDef::formula(
+            "毛利率",
+            "([零售价]-[成本价])/[零售价]",
+            "0.00%",
+        ))
+        .unwrap();
+        assert_eq!(formula["type"], 20);
+        assert_eq!(formula["property"]["formula_expression"], "([零售价]-[成本价])/[零售价]");
+
+        let lookup = serde_json::to_value(FieldDef::lookup("产地", "tblB", "fldO")).unwrap();
+        assert_eq!(lookup["type"], 19);
+        assert_eq!(lookup["property"]["target_field"], "fldO");
+
+        assert_eq!(serde_json::to_value(FieldDef::modified_time("更新时间")).unwrap()["type"], 1002);
+        assert_eq!(serde_json::to_value(FieldDef::multi_select("标签", &["a"])).unwrap()["type"], 4);
+    }
+}
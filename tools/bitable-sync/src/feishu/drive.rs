@@ -0,0 +1,314 @@
+use anyhow::{Context, Result};
+use reqwest::multipart::{Form, Part};
+use serde::Deserialize;
+use std::path::Path;
+use tokio::io::AsyncReadExt;
+
+use super::api::{FeishuApi, Idempotency};
+use super::auth::FeishuAuth;
+
+#[derive(Debug, Deserialize)]
+struct TmpDownloadData {
+    tmp_download_urls: Vec<TmpDownloadUrl>,
+}
+
+#[derive(Debug, Deserialize)]
+struct TmpDownloadUrl {
+    #[allow(dead_code)]
+    file_token: String,
+    tmp_download_url: String,
+}
+
+/// Files up to this size go through `upload_all`; larger ones are chunked
+const UPLOAD_ALL_LIMIT: u64 = 20 * 1024 * 1024;
+
+/// Where an uploaded media file will be attached
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[allow(dead_code)]
+pub enum MediaParent {
+    /// Image attachment in a Bitable app (shows a thumbnail)
+    BitableImage,
+    /// Any other attachment in a Bitable app (videos, PDFs, ...)
+    BitableFile,
+}
+
+impl MediaParent {
+    fn as_str(self) -> &'static str {
+        match self {
+            MediaParent::BitableImage => "bitable_image",
+            MediaParent::BitableFile => "bitable_file",
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct UploadData {
+    file_token: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct UploadPrepareData {
+    upload_id: String,
+    block_size: u64,
+    block_num: u64,
+}
+
+/// Client for Feishu Drive media endpoints (attachment files)
+#[derive(Clone)]
+pub struct DriveClient {
+    api: FeishuApi,
+}
+
+impl DriveClient {
+    pub fn new(auth: FeishuAuth) -> Self {
+        Self {
+            api: FeishuApi::new(auth),
+        }
+    }
+
+    /// Resolve a file_token to a temporary download URL.
+    /// The returned URL is a direct download link valid for ~30 minutes.
+    pub async fn tmp_download_url(&self, file_token: &str) -> Result<String> {
+        let url = format!(
+            "{}/drive/v1/medias/batch_get_tmp_download_url",
+            self.api.base_url()
+        );
+
+        let data = self
+            .api
+            .send::<TmpDownloadData, _>(
+                &format!("get download URL for {}", file_token),
+                Idempotency::Idempotent,
+                |c| c.get(&url).query(&[("file_tokens", file_token)]),
+            )
+            .await?
+            .context("No data in download URL response")?;
+
+        let dl = data
+            .tmp_download_urls
+            .into_iter()
+            .next()
+            .context("Empty tmp_download_urls array")?;
+
+        tracing::debug!("Resolved {} -> download URL", file_token);
+        Ok(dl.tmp_download_url)
+    }
+
+    /// Upload a local file as media attached to `parent_node` (the Bitable
+    /// app_token), returning the file_token to put in an attachment field.
+    /// Files over 20MB are uploaded in parts.
+    pub async fn upload_file(
+        &self,
+        path: &Path,
+        parent: MediaParent,
+        parent_node: &str,
+    ) -> Result<String> {
+        let file_name = path
+            .file_name()
+            .and_then(|n| n.to_str())
+            .with_context(|| format!("Invalid file name: {}", path.display()))?
+            .to_string();
+        let size = tokio::fs::metadata(path)
+            .await
+            .with_context(|| format!("Failed to stat {}", path.display()))?
+            .len();
+
+        let file_token = if size <= UPLOAD_ALL_LIMIT {
+            let bytes = tokio::fs::read(path)
+                .await
+                .with_context(|| format!("Failed to read {}", path.display()))?;
+            self.upload_all(&file_name, bytes, parent, parent_node).await?
+        } else {
+            self.upload_chunked(path, &file_name, size, parent, parent_node)
+                .await?
+        };
+
+        tracing::debug!("Uploaded {} ({} bytes) -> {}", path.display(), size, file_token);
+        Ok(file_token)
+    }
+
+    /// Single-request upload (`medias/upload_all`)
+    async fn upload_all(
+        &self,
+        file_name: &str,
+        bytes: Vec<u8>,
+        parent: MediaParent,
+        parent_node: &str,
+    ) -> Result<String> {
+        let url = format!("{}/drive/v1/medias/upload_all", self.api.base_url());
+        let size = bytes.len().to_string();
+
+        let data = self
+            .api
+            .send::<UploadData, _>(&format!("upload {}", file_name), Idempotency::NonIdempotent, |c| {
+                let form = Form::new()
+                    .text("file_name", file_name.to_string())
+                    .text("parent_type", parent.as_str())
+                    .text("parent_node", parent_node.to_string())
+                    .text("size", size.clone())
+                    .part("file", Part::bytes(bytes.clone()).file_name(file_name.to_string()));
+                c.post(&url).multipart(form)
+            })
+            .await?
+            .context("No data in upload response")?;
+        Ok(data.file_token)
+    }
+
+    /// Chunked upload: `upload_prepare`, one `upload_part` per block, `upload_finish`
+    async fn upload_chunked(
+        &self,
+        path: &Path,
+        file_name: &str,
+        size: u64,
+        parent: MediaParent,
+        parent_node: &str,
+    ) -> Result<String> {
+        let base = format!("{}/drive/v1/medias", self.api.base_url());
+
+        let prepare_url = format!("{}/upload_prepare", base);
+        let body = serde_json::json!({
+            "file_name": file_name,
+            "parent_type": parent.as_str(),
+            "parent_node": parent_node,
+            "size": size,
+        });
+        let plan = self
+            .api
+            .send::<UploadPrepareData, _>(
+                &format!("prepare upload of {}", file_name),
+                Idempotency::Idempotent,
+                |c| c.post(&prepare_url).json(&body),
+            )
+            .await?
+            .context("No data in upload_prepare response")?;
+        tracing::info!(
+            "Uploading {} in {} parts of {} bytes",
+            file_name,
+            plan.block_num,
+            plan.block_size
+        );
+
+        let mut file = tokio::fs::File::open(path)
+            .await
+            .with_context(|| format!("Failed to open {}", path.display()))?;
+        let part_url = format!("{}/upload_part", base);
+        for seq in 0..plan.block_num {
+            let len = plan.block_size.min(size - seq * plan.block_size) as usize;
+            let mut block = vec![0u8; len];
+            file.read_exact(&mut block)
+                .await
+                .with_context(|| format!("Failed to read part {} of {}", seq, path.display()))?;
+
+            self.api
+                .send::<serde_json::Value, _>(
+                    &format!("upload part {}/{} of {}", seq + 1, plan.block_num, file_name),
+                    // Resending a part overwrites it
+                    Idempotency::Idempotent,
+                    |c| {
+                        let form = Form::new()
+                            .text("upload_id", plan.upload_id.clone())
+                            .text("seq", seq.to_string())
+                            .text("size", len.to_string())
+                            .part("file", Part::bytes(block.clone()));
+                        c.post(&part_url).multipart(form)
+                    },
+                )
+                .await?;
+        }
+
+        let finish_url = format!("{}/upload_finish", base);
+        let body = serde_json::json!({"upload_id": plan.upload_id, "block_num": plan.block_num});
+        let data = self
+            .api
+            .send::<UploadData, _>(
+                &format!("finish upload of {}", file_name),
+                Idempotency::NonIdempotent,
+                |c| c.post(&finish_url).json(&body),
+            )
+            .await?
+            .context("No data in upload_finish response")?;
+        Ok(data.file_token)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::feishu::stub::{token_response, StubResponse, StubServer};
+    use crate::testing::TempDir;
+
+    fn ok(data: serde_json::Value) -> StubResponse {
+        StubResponse::json(serde_json::json!({"code": 0, "msg": "ok", "data": data}))
+    }
+
+    fn temp_file(dir: &TempDir, name: &str, bytes: &[u8]) -> std::path::PathBuf {
+        let path = dir.path().join(name);
+        std::fs::write(&path, bytes).unwrap();
+        path
+    }
+
+    #[tokio::test]
+    async fn small_files_use_upload_all() {
+        let server = StubServer::start(|req| {
+            if req.path.starts_with("/auth/") {
+                return token_response("t", 7200);
+            }
+            ok(serde_json::json!({"file_token": "boxImg"}))
+        })
+        .await;
+        let drive = DriveClient::new(FeishuAuth::new(&server.base_url, "id".into(), "secret".into()));
+        let dir = TempDir::new("drive");
+        let path = temp_file(&dir, "p001.jpg", b"jpeg-bytes");
+
+        let token = drive
+            .upload_file(&path, MediaParent::BitableImage, "bascnApp")
+            .await
+            .unwrap();
+
+        assert_eq!(token, "boxImg");
+        let upload = server
+            .requests()
+            .into_iter()
+            .find(|r| r.path == "/drive/v1/medias/upload_all")
+            .unwrap();
+        assert!(upload.body.contains("bitable_image"));
+        assert!(upload.body.contains("bascnApp"));
+        assert!(upload.body.contains("jpeg-bytes"));
+    }
+
+    #[tokio::test]
+    async fn chunked_upload_sends_every_part() {
+        let server = StubServer::start(|req| {
+            if req.path.starts_with("/auth/") {
+                return token_response("t", 7200);
+            }
+            match req.path.as_str() {
+                "/drive/v1/medias/upload_prepare" => ok(serde_json::json!({
+                    "upload_id": "up1", "block_size": 4, "block_num": 3
+                })),
+                "/drive/v1/medias/upload_finish" => ok(serde_json::json!({"file_token": "boxVid"})),
+                _ => ok(serde_json::json!({})),
+            }
+        })
+        .await;
+        let drive = DriveClient::new(FeishuAuth::new(&server.base_url, "id".into(), "secret".into()));
+        let dir = TempDir::new("drive");
+        let path = temp_file(&dir, "clip.mp4", b"wwwwxxxxyy");
+
+        let token = drive
+            .upload_chunked(&path, "clip.mp4", 10, MediaParent::BitableFile, "bascnApp")
+            .await
+            .unwrap();
+
+        assert_eq!(token, "boxVid");
+        assert_eq!(server.count("/drive/v1/medias/upload_part"), 3);
+        let last = server
+            .requests()
+            .into_iter()
+            .filter(|r| r.path == "/drive/v1/medias/upload_part")
+            .find(|r| r.body.contains("yy"))
+            .unwrap();
+        assert!(!last.body.contains("xxxx"));
+        assert!(server.requests().iter().any(|r| r.body.contains(r#""block_num":3"#)));
+    }
+}
@@ -1,2 +1,8 @@
+pub mod api;
 pub mod auth;
 pub mod bitable;
+pub mod drive;
+pub mod search;
+
+#[cfg(test)]
+pub mod stub;
@@ -0,0 +1,239 @@
+use serde::Serialize;
+
+// ============================================================
+// Filter builder for records/search
+// ============================================================
+
+/// Comparison operators supported by Bitable record search
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+#[allow(dead_code)]
+pub enum Operator {
+    Is,
+    IsNot,
+    Contains,
+    DoesNotContain,
+    IsEmpty,
+    IsNotEmpty,
+    IsGreater,
+    IsGreaterEqual,
+    IsLess,
+    IsLessEqual,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Conjunction {
+    And,
+    Or,
+}
+
+/// A single `field operator value` condition
+#[derive(Debug, Clone, Serialize)]
+pub struct Condition {
+    pub field_name: String,
+    pub operator: Operator,
+    pub value: Vec<String>,
+}
+
+#[allow(dead_code)]
+impl Condition {
+    pub fn new(field_name: &str, operator: Operator, value: &[&str]) -> Self {
+        Self {
+            field_name: field_name.to_string(),
+            operator,
+            value: value.iter().map(|v| v.to_string()).collect(),
+        }
+    }
+
+    pub fn is(field_name: &str, value: &str) -> Self {
+        Self::new(field_name, Operator::Is, &[value])
+    }
+
+    pub fn is_not(field_name: &str, value: &str) -> Self {
+        Self::new(field_name, Operator::IsNot, &[value])
+    }
+
+    pub fn contains(field_name: &str, value: &str) -> Self {
+        Self::new(field_name, Operator::Contains, &[value])
+    }
+
+    pub fn is_empty(field_name: &str) -> Self {
+        Self::new(field_name, Operator::IsEmpty, &[])
+    }
+
+    pub fn is_not_empty(field_name: &str) -> Self {
+        Self::new(field_name, Operator::IsNotEmpty, &[])
+    }
+
+    pub fn greater_than(field_name: &str, value: &str) -> Self {
+        Self::new(field_name, Operator::IsGreater, &[value])
+    }
+
+    pub fn less_than(field_name: &str, value: &str) -> Self {
+        Self::new(field_name, Operator::IsLess, &[value])
+    }
+}
+
+/// A group of conditions joined by AND/OR.
+///
+/// Feishu allows one level of nesting: the top-level filter may hold
+/// `children` groups, but those groups cannot have children of their own.
+#[derive(Debug, Clone, Serialize)]
+pub struct Filter {
+    pub conjunction: Conjunction,
+    pub conditions: Vec<Condition>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub children: Vec<Filter>,
+}
+
+#[allow(dead_code)]
+impl Filter {
+    pub fn and() -> Self {
+        Self {
+            conjunction: Conjunction::And,
+            conditions: Vec::new(),
+            children: Vec::new(),
+        }
+    }
+
+    pub fn or() -> Self {
+        Self {
+            conjunction: Conjunction::Or,
+            ..Self::and()
+        }
+    }
+
+    pub fn condition(mut self, condition: Condition) -> Self {
+        self.conditions.push(condition);
+        self
+    }
+
+    /// Add a nested group (only valid on the top-level filter)
+    pub fn child(mut self, child: Filter) -> Self {
+        debug_assert!(
+            child.children.is_empty(),
+            "Feishu filters nest one level only"
+        );
+        self.children.push(child);
+        self
+    }
+}
+
+/// Sort spec for records/search
+#[derive(Debug, Clone, Serialize)]
+pub struct Sort {
+    pub field_name: String,
+    pub desc: bool,
+}
+
+#[allow(dead_code)]
+impl Sort {
+    pub fn asc(field_name: &str) -> Self {
+        Self {
+            field_name: field_name.to_string(),
+            desc: false,
+        }
+    }
+
+    pub fn desc(field_name: &str) -> Self {
+        Self {
+            field_name: field_name.to_string(),
+            desc: true,
+        }
+    }
+}
+
+/// Request body for `BitableClient::search_records`
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct SearchOptions {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub view_id: Option<String>,
+    /// Only return these columns (all columns when `None`)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub field_names: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub sort: Vec<Sort>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub filter: Option<Filter>,
+    /// Also return created/modified time and user per record
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub automatic_fields: bool,
+}
+
+#[allow(dead_code)]
+impl SearchOptions {
+    pub fn view(mut self, view_id: &str) -> Self {
+        self.view_id = Some(view_id.to_string());
+        self
+    }
+
+    pub fn fields(mut self, names: &[&str]) -> Self {
+        self.field_names = Some(names.iter().map(|n| n.to_string()).collect());
+        self
+    }
+
+    pub fn sort(mut self, sort: Sort) -> Self {
+        self.sort.push(sort);
+        self
+    }
+
+    pub fn filter(mut self, filter: Filter) -> Self {
+        self.filter = Some(filter);
+        self
+    }
+
+    pub fn automatic_fields(mut self) -> Self {
+        self.automatic_fields = true;
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn serializes_nested_filter() {
+        let opts = SearchOptions::default()
+            .fields(&["商品ID", "状态"])
+            .sort(Sort::asc("排序"))
+            .filter(
+                Filter::and()
+                    .condition(Condition::is_not_empty("商品ID"))
+                    .child(
+                        Filter::or()
+                            .condition(Condition::is("状态", "active"))
+                            .condition(Condition::is_empty("状态")),
+                    ),
+            );
+
+        let json = serde_json::to_value(&opts).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({
+                "field_names": ["商品ID", "状态"],
+                "sort": [{"field_name": "排序", "desc": false}],
+                "filter": {
+                    "conjunction": "and",
+                    "conditions": [
+                        {"field_name": "商品ID", "operator": "isNotEmpty", "value": []}
+                    ],
+                    "children": [{
+                        "conjunction": "or",
+                        "conditions": [
+                            {"field_name": "状态", "operator": "is", "value": ["active"]},
+                            {"field_name": "状态", "operator": "isEmpty", "value": []}
+                        ]
+                    }]
+                }
+            })
+        );
+    }
+
+    #[test]
+    fn empty_options_serialize_to_empty_object() {
+        let json = serde_json::to_value(SearchOptions::default()).unwrap();
+        assert_eq!(json, serde_json::json!({}));
+    }
+}
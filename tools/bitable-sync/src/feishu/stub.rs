@@ -0,0 +1,184 @@
+//! Minimal local stand-in for the Feishu Open API, used by tests.
+//!
+//! Speaks just enough HTTP/1.1 for reqwest: one request per connection,
+//! answered by a caller-supplied handler.
+
+// Not every test uses every helper
+#![allow(dead_code)]
+
+use std::sync::{Arc, Mutex};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpListener;
+
+/// A request as seen by the stub
+#[derive(Debug, Clone)]
+pub struct StubRequest {
+    pub method: String,
+    /// Path including query string, with the `/open-apis` prefix stripped
+    pub path: String,
+    pub headers: Vec<(String, String)>,
+    pub body: String,
+}
+
+impl StubRequest {
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(k, _)| k.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_str())
+    }
+}
+
+/// A canned response
+pub struct StubResponse {
+    pub status: u16,
+    pub headers: Vec<(String, String)>,
+    pub body: String,
+}
+
+impl StubResponse {
+    pub fn json(body: serde_json::Value) -> Self {
+        Self {
+            status: 200,
+            headers: vec![],
+            body: body.to_string(),
+        }
+    }
+
+    pub fn status(mut self, status: u16) -> Self {
+        self.status = status;
+        self
+    }
+
+    pub fn header(mut self, name: &str, value: &str) -> Self {
+        self.headers.push((name.to_string(), value.to_string()));
+        self
+    }
+}
+
+type Handler = dyn Fn(&StubRequest) -> StubResponse + Send + Sync;
+
+pub struct StubServer {
+    /// Open API root to hand to `FeishuAuth::new`
+    pub base_url: String,
+    requests: Arc<Mutex<Vec<StubRequest>>>,
+}
+
+impl StubServer {
+    pub async fn start<F>(handler: F) -> Self
+    where
+        F: Fn(&StubRequest) -> StubResponse + Send + Sync + 'static,
+    {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let handler: Arc<Handler> = Arc::new(handler);
+
+        let log = requests.clone();
+        tokio::spawn(async move {
+            while let Ok((mut socket, _)) = listener.accept().await {
+                let log = log.clone();
+                let handler = handler.clone();
+                tokio::spawn(async move {
+                    let Some(req) = read_request(&mut socket).await else {
+                        return;
+                    };
+                    log.lock().unwrap().push(req.clone());
+                    let resp = handler(&req);
+                    let mut out = format!(
+                        "HTTP/1.1 {} Stub\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n",
+                        resp.status,
+                        resp.body.len()
+                    );
+                    for (k, v) in &resp.headers {
+                        out.push_str(&format!("{}: {}\r\n", k, v));
+                    }
+                    out.push_str("\r\n");
+                    out.push_str(&resp.body);
+                    let _ = socket.write_all(out.as_bytes()).await;
+                    let _ = socket.shutdown().await;
+                });
+            }
+        });
+
+        Self {
+            base_url: format!("http://{}/open-apis", addr),
+            requests,
+        }
+    }
+
+    /// All requests received so far
+    pub fn requests(&self) -> Vec<StubRequest> {
+        self.requests.lock().unwrap().clone()
+    }
+
+    /// Number of requests whose path starts with `prefix`
+    pub fn count(&self, prefix: &str) -> usize {
+        self.requests()
+            .iter()
+            .filter(|r| r.path.starts_with(prefix))
+            .count()
+    }
+}
+
+async fn read_request(socket: &mut tokio::net::TcpStream) -> Option<StubRequest> {
+    let mut buf = Vec::new();
+    let mut chunk = [0u8; 4096];
+
+    let header_end = loop {
+        let n = socket.read(&mut chunk).await.ok()?;
+        if n == 0 {
+            return None;
+        }
+        buf.extend_from_slice(&chunk[..n]);
+        if let Some(pos) = buf.windows(4).position(|w| w == b"\r\n\r\n") {
+            break pos + 4;
+        }
+    };
+
+    let head = String::from_utf8_lossy(&buf[..header_end]).to_string();
+    let mut lines = head.split("\r\n");
+    let mut request_line = lines.next()?.split(' ');
+    let method = request_line.next()?.to_string();
+    let raw_path = request_line.next()?;
+    let path = raw_path
+        .strip_prefix("/open-apis")
+        .unwrap_or(raw_path)
+        .to_string();
+
+    let headers: Vec<(String, String)> = lines
+        .filter_map(|l| l.split_once(':'))
+        .map(|(k, v)| (k.trim().to_string(), v.trim().to_string()))
+        .collect();
+
+    let content_length = headers
+        .iter()
+        .find(|(k, _)| k.eq_ignore_ascii_case("content-length"))
+        .and_then(|(_, v)| v.parse::<usize>().ok())
+        .unwrap_or(0);
+
+    while buf.len() < header_end + content_length {
+        let n = socket.read(&mut chunk).await.ok()?;
+        if n == 0 {
+            break;
+        }
+        buf.extend_from_slice(&chunk[..n]);
+    }
+
+    Some(StubRequest {
+        method,
+        path,
+        headers,
+        body: String::from_utf8_lossy(&buf[header_end..]).to_string(),
+    })
+}
+
+/// Response body for a successful tenant_access_token request
+pub fn token_response(token: &str, expire: i64) -> StubResponse {
+    StubResponse::json(serde_json::json!({
+        "code": 0,
+        "msg": "ok",
+        "tenant_access_token": token,
+        "expire": expire,
+    }))
+}
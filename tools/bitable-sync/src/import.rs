@@ -0,0 +1,802 @@
+//! Seed a base from the site's own data files: `productDatabase.json`
+//! (brands, categories, products) and `mockData.ts` (display categories,
+//! media, store info, slogans), the inverse of what `sync` writes.
+//!
+//! Records are upserted on their id (or title/text for tables without one),
+//! so a re-run updates rows instead of duplicating them. Local images under
+//! public/ are uploaded on every run.
+
+use anyhow::{Context, Result};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use crate::columns::ColumnMap;
+use crate::config::{Config, TableKind};
+use crate::feishu::auth::FeishuAuth;
+use crate::feishu::bitable::BitableClient;
+use crate::feishu::drive::{DriveClient, MediaParent};
+use crate::feishu::search::SearchOptions;
+use crate::models::bitable_value::RecordFields;
+use crate::models::mock_data::{
+    DisplayCategory, MediaItem, MockData, MockProduct, Slogan, StoreInfo,
+};
+use crate::models::product::{Product, ProductDatabase};
+use crate::schema::{table_spec, TableSpec};
+
+pub struct ImportOptions {
+    /// Only print what would be written
+    pub dry_run: bool,
+}
+
+/// Field map of one record, keyed by model field; `Writer` maps the keys
+/// to the table's columns
+struct Row {
+    spec: &'static TableSpec,
+    fields: HashMap<String, Value>,
+}
+
+impl Row {
+    fn new(kind: TableKind) -> Self {
+        Self {
+            spec: table_spec(kind),
+            fields: HashMap::new(),
+        }
+    }
+
+    /// Set a cell, leaving it out when empty
+    fn set(&mut self, model: &str, value: impl Into<Value>) {
+        let value = value.into();
+        if value.is_null() || value == "" {
+            return;
+        }
+        // Panics on a model field schema.toml doesn't map
+        self.spec.column(model);
+        self.fields.insert(model.to_string(), value);
+    }
+}
+
+/// Uploads local images once per run and hands out attachment cells
+struct Images {
+    drive: DriveClient,
+    app_token: String,
+    public_dir: PathBuf,
+    dry_run: bool,
+    uploaded: HashMap<PathBuf, String>,
+    missing: Vec<String>,
+}
+
+impl Images {
+    /// Attachment cell for a site image path (`images/...`); `None` for
+    /// remote URLs and files that don't exist locally
+    async fn cell(&mut self, url: &str) -> Result<Option<Value>> {
+        if url.is_empty() || url.starts_with("http://") || url.starts_with("https://") {
+            return Ok(None);
+        }
+        let path = self.public_dir.join(url.trim_start_matches('/'));
+        if !path.is_file() {
+            self.missing.push(url.to_string());
+            return Ok(None);
+        }
+        if self.dry_run {
+            println!("  would upload {}", path.display());
+            return Ok(None);
+        }
+        if !self.uploaded.contains_key(&path) {
+            let token = self
+                .drive
+                .upload_file(&path, MediaParent::BitableImage, &self.app_token)
+                .await
+                .with_context(|| format!("Failed to upload {}", path.display()))?;
+            self.uploaded.insert(path.clone(), token);
+        }
+        Ok(Some(json!([{"file_token": self.uploaded[&path]}])))
+    }
+}
+
+/// Read productDatabase.json and mockData.ts and upsert them into the base
+pub async fn import(config: &Config, opts: &ImportOptions) -> Result<()> {
+    let db_path = config.data_dir().join("productDatabase.json");
+    let content = std::fs::read_to_string(&db_path)
+        .with_context(|| format!("Failed to read {}", db_path.display()))?;
+    let db: ProductDatabase = serde_json::from_str(&content)
+        .with_context(|| format!("Failed to parse {}", db_path.display()))?;
+    let mock = load_mock_data(&config.data_dir().join("mockData.ts"))?;
+
+    let auth = FeishuAuth::from_config(config);
+    let client = BitableClient::new(auth.clone(), config.bitable_app_token.clone());
+    let mut images = Images {
+        drive: DriveClient::new(auth),
+        app_token: config.bitable_app_token.clone(),
+        public_dir: config.public_dir(),
+        dry_run: opts.dry_run,
+        uploaded: HashMap::new(),
+        missing: Vec::new(),
+    };
+    let mut writer = Writer {
+        client: &client,
+        config,
+        dry_run: opts.dry_run,
+        failed: 0,
+    };
+
+    // Brands and categories first: products link to them
+    let mut brands = Vec::new();
+    for brand in &db.brands {
+        let mut row = Row::new(TableKind::Brands);
+        row.set("id", brand.id.as_str());
+        row.set("name", brand.name.as_str());
+        row.set("story", brand.story.clone());
+        row.set("founded_year", brand.founded_year);
+        row.set("origin", brand.origin.clone());
+        if let Some(logo) = images
+            .cell(brand.logo.as_deref().unwrap_or_default())
+            .await?
+        {
+            row.set("logo", logo);
+        }
+        brands.push(row);
+    }
+    writer.write(TableKind::Brands, "id", brands).await?;
+
+    let categories: Vec<Row> = display_categories(&db, mock.as_ref())
+        .iter()
+        .map(category_row)
+        .collect();
+    writer
+        .write(TableKind::DisplayCategories, "id", categories)
+        .await?;
+
+    let brand_ids = writer.record_ids(TableKind::Brands).await?;
+    let category_ids = writer.record_ids(TableKind::DisplayCategories).await?;
+    let mock_products = mock.as_ref().map_or(&[][..], |m| m.products.as_slice());
+    let mut products = Vec::new();
+    for (i, product) in db.products.iter().enumerate() {
+        let mut row = product_row(product, i, mock_products, &brand_ids, &category_ids);
+        if let Some(image) = images.cell(&product.main_image).await? {
+            row.set("main_image", image);
+        }
+        products.push(row);
+    }
+    writer.write(TableKind::Products, "id", products).await?;
+
+    if let Some(mock) = &mock {
+        let mut media = Vec::new();
+        for (i, item) in mock.media_playlist.iter().enumerate() {
+            if item.media_type == "video" {
+                // Only the HLS segments are kept locally, not the source video
+                tracing::warn!(
+                    "Skipping video '{}': upload its source file to the media table by hand",
+                    item.title.as_deref().unwrap_or(&item.url)
+                );
+                continue;
+            }
+            let mut row = media_row(item, i);
+            if let Some(file) = images.cell(&item.url).await? {
+                row.set("file", file);
+            }
+            media.push(row);
+        }
+        writer.write(TableKind::Media, "title", media).await?;
+
+        let mut store = store_row(&mock.store_info);
+        if let Some(qr) = images.cell(&mock.store_info.qr_code_url).await? {
+            store.set("qr_code", qr);
+        }
+        writer
+            .write(TableKind::StoreInfo, "name", vec![store])
+            .await?;
+
+        if config.table_id(TableKind::Slogans).is_empty() {
+            tracing::warn!("No slogans table, skipping slogans");
+        } else {
+            let slogans = mock.slogans.iter().enumerate().map(slogan_row).collect();
+            writer.write(TableKind::Slogans, "text", slogans).await?;
+        }
+    }
+
+    if !images.missing.is_empty() {
+        tracing::warn!(
+            "Images not found under public/: {}",
+            images.missing.join(", ")
+        );
+    }
+    anyhow::ensure!(
+        writer.failed == 0,
+        "{} records could not be written",
+        writer.failed
+    );
+    if opts.dry_run {
+        println!("\nDry run, nothing was written.");
+    } else {
+        println!("\nImport complete.");
+    }
+    Ok(())
+}
+
+/// Upserts rows table by table and keeps count of failures
+struct Writer<'a> {
+    client: &'a BitableClient,
+    config: &'a Config,
+    dry_run: bool,
+    failed: usize,
+}
+
+impl Writer<'_> {
+    async fn write(&mut self, kind: TableKind, key_model: &str, rows: Vec<Row>) -> Result<()> {
+        let table_id = self.config.table_id(kind);
+        let spec = table_spec(kind);
+
+        let fields = self.client.list_fields(table_id).await?;
+        let columns: Vec<String> = fields.iter().map(|f| f.field_name.clone()).collect();
+        let cols = ColumnMap::for_fields(self.config, kind, &fields);
+        let (records, dropped) = to_columns(rows, &cols, &columns);
+        if !dropped.is_empty() {
+            tracing::warn!(
+                "{} has no column {}, not importing it",
+                spec.name,
+                dropped.join(", ")
+            );
+        }
+
+        if self.dry_run {
+            println!("{}: would upsert {} records", spec.name, records.len());
+            return Ok(());
+        }
+        let report = self
+            .client
+            .upsert_records(table_id, cols.get(key_model), &records)
+            .await?;
+        println!(
+            "{}: {} created, {} updated, {} failed",
+            spec.name,
+            report.created,
+            report.updated,
+            report.failed.len()
+        );
+        self.failed += report.failed.len();
+        Ok(())
+    }
+
+    /// Map the table's ids (its `id` column) to record ids, for link cells
+    async fn record_ids(&self, kind: TableKind) -> Result<HashMap<String, String>> {
+        let cols = ColumnMap::load(self.client, self.config, kind).await?;
+        let column = cols.get("id");
+        let query = SearchOptions::default().fields(&[column]);
+        let records = self
+            .client
+            .search_records(self.config.table_id(kind), &query)
+            .await?;
+        let mut ids = HashMap::new();
+        for record in records {
+            if let Some(id) = RecordFields::new(&record.fields).text(column)? {
+                ids.entry(id).or_insert(record.record_id);
+            }
+        }
+        Ok(ids)
+    }
+}
+
+/// Key each row's cells by the column its model field maps to. Older bases
+/// may lack optional columns and writing them would fail, so cells without
+/// a column are left out; also returns those columns.
+fn to_columns(
+    rows: Vec<Row>,
+    cols: &ColumnMap,
+    available: &[String],
+) -> (Vec<HashMap<String, Value>>, Vec<String>) {
+    let mut dropped: Vec<String> = Vec::new();
+    let records = rows
+        .into_iter()
+        .map(|row| {
+            let mut fields = HashMap::new();
+            for (model, value) in row.fields {
+                let column = cols.get(&model);
+                if available.iter().any(|c| c == column) {
+                    fields.insert(column.to_string(), value);
+                } else if !dropped.iter().any(|c| c == column) {
+                    dropped.push(column.to_string());
+                }
+            }
+            fields
+        })
+        .collect();
+    (records, dropped)
+}
+
+/// mockData.ts categories (with icons, in display order), then any
+/// productDatabase.json category not among them
+fn display_categories(db: &ProductDatabase, mock: Option<&MockData>) -> Vec<DisplayCategory> {
+    let mut categories: Vec<DisplayCategory> =
+        mock.map(|m| m.categories.clone()).unwrap_or_default();
+    for category in &db.categories {
+        if !categories.iter().any(|c| c.id == category.id) {
+            categories.push(DisplayCategory {
+                id: category.id.clone(),
+                name: category.name.clone(),
+                icon: category.icon.clone(),
+                sort_order: 0,
+            });
+        }
+    }
+    for (i, category) in categories.iter_mut().enumerate() {
+        category.sort_order = i as i32 + 1;
+    }
+    categories
+}
+
+fn category_row(category: &DisplayCategory) -> Row {
+    let mut row = Row::new(TableKind::DisplayCategories);
+    row.set("id", category.id.as_str());
+    row.set("name", category.name.as_str());
+    row.set("icon", category.icon.clone());
+    row.set("sort_order", category.sort_order);
+    row
+}
+
+fn product_row(
+    product: &Product,
+    position: usize,
+    mock_products: &[MockProduct],
+    brand_ids: &HashMap<String, String>,
+    category_ids: &HashMap<String, String>,
+) -> Row {
+    let mut row = Row::new(TableKind::Products);
+    row.set("id", product.id.as_str());
+    row.set("sku", product.sku.as_str());
+    row.set("name", product.name.as_str());
+    row.set("specification", product.specification.as_str());
+    row.set("unit", product.unit.as_str());
+    row.set("retail_price", product.retail_price);
+    row.set("cost_price", product.cost_price);
+    row.set("member_price", product.member_price);
+    row.set("promotion_price", product.promotion_price);
+    row.set("stock", product.stock);
+    row.set("alcohol_content", product.alcohol_content);
+    row.set("vintage", product.vintage);
+    row.set("brewing_process", product.brewing_process.as_str());
+    row.set("flavor_profile", product.flavor_profile.as_str());
+    row.set("short_description", product.short_description.as_str());
+    row.set("long_description", product.long_description.clone());
+    row.set("status", product.status.as_str());
+    row.set("is_hot", product.is_hot);
+    row.set("is_new", product.is_new);
+    row.set("is_promotion", product.is_promotion);
+    row.set("sort_order", position as i32 + 1);
+    let display: Vec<&str> = mock_products
+        .iter()
+        .filter(|m| m.id == product.id)
+        .map(|m| m.category_id.as_str())
+        .collect();
+    row.set("display_category_ids", display.join(","));
+    if let Ok(listed) = chrono::DateTime::parse_from_rfc3339(&product.created_at) {
+        row.set("listed_at", listed.timestamp_millis());
+    }
+    if let Some(record) = brand_ids.get(&product.brand.id) {
+        row.set("brand", json!([record]));
+    }
+    if let Some(record) = category_ids.get(&product.category.id) {
+        row.set("category", json!([record]));
+    }
+    row
+}
+
+fn media_row(item: &MediaItem, position: usize) -> Row {
+    let mut row = Row::new(TableKind::Media);
+    // The title is the upsert key, so untitled images get their file name
+    let title = item.title.clone().unwrap_or_else(|| {
+        Path::new(&item.url)
+            .file_stem()
+            .map(|s| s.to_string_lossy().into_owned())
+            .unwrap_or_default()
+    });
+    row.set("title", title);
+    row.set("media_type", item.media_type.as_str());
+    row.set("duration", item.duration);
+    row.set("sort_order", position as i32 + 1);
+    row
+}
+
+fn store_row(store: &StoreInfo) -> Row {
+    let mut row = Row::new(TableKind::StoreInfo);
+    row.set("name", store.name.as_str());
+    row.set("phone", store.phone.as_str());
+    row
+}
+
+fn slogan_row((position, slogan): (usize, &Slogan)) -> Row {
+    let mut row = Row::new(TableKind::Slogans);
+    row.set("text", slogan.text.as_str());
+    row.set("sort_order", position as i32 + 1);
+    row.set("enabled", true);
+    row
+}
+
+// ============================================================
+// mockData.ts reader
+// ============================================================
+
+/// Read a mockData.ts written by `ts_writer`; `None` if there is none
+fn load_mock_data(path: &Path) -> Result<Option<MockData>> {
+    if !path.exists() {
+        tracing::warn!(
+            "{} not found, importing productDatabase.json only",
+            path.display()
+        );
+        return Ok(None);
+    }
+    let content = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read {}", path.display()))?;
+    parse_mock_data(&content)
+        .with_context(|| format!("Failed to parse {}", path.display()))
+        .map(Some)
+}
+
+fn parse_mock_data(src: &str) -> Result<MockData> {
+    let consts = parse_ts_consts(src)?;
+    let get = |name: &str| consts.get(name).cloned().unwrap_or(Value::Null);
+    let text = |v: &Value, key: &str| v[key].as_str().map(str::to_string);
+    let list = |name: &str| get(name).as_array().cloned().unwrap_or_default();
+
+    let store = get("storeInfo");
+    Ok(MockData {
+        store_info: StoreInfo {
+            name: text(&store, "name").unwrap_or_default(),
+            phone: text(&store, "phone").unwrap_or_default(),
+            qr_code_url: text(&store, "qrCodeUrl").unwrap_or_default(),
+            qr_file_token: None,
+        },
+        media_playlist: list("mediaPlaylist")
+            .iter()
+            .enumerate()
+            .map(|(i, m)| MediaItem {
+                media_type: text(m, "type").unwrap_or_else(|| "image".to_string()),
+                url: text(m, "url").unwrap_or_default(),
+                title: text(m, "title"),
+                duration: m["duration"].as_i64(),
+                sort_order: i as i32 + 1,
+            })
+            .collect(),
+        categories: list("categories")
+            .iter()
+            .enumerate()
+            .map(|(i, c)| DisplayCategory {
+                id: text(c, "id").unwrap_or_default(),
+                name: text(c, "name").unwrap_or_default(),
+                icon: text(c, "icon"),
+                sort_order: i as i32 + 1,
+            })
+            .collect(),
+        products: list("products")
+            .iter()
+            .map(|p| MockProduct {
+                id: text(p, "id").unwrap_or_default(),
+                name: text(p, "name").unwrap_or_default(),
+                description: text(p, "description").unwrap_or_default(),
+                price: p["price"].as_f64().unwrap_or(0.0),
+                image: text(p, "image").unwrap_or_default(),
+                category_id: text(p, "categoryId").unwrap_or_default(),
+            })
+            .collect(),
+        slogans: list("slogans")
+            .iter()
+            .enumerate()
+            .map(|(i, s)| Slogan {
+                text: text(s, "text").unwrap_or_default(),
+                sort_order: i as i32 + 1,
+            })
+            .collect(),
+    })
+}
+
+/// Values of the `export const name: Type = <literal>;` declarations.
+/// Handles the object/array/string/number literals `ts_writer` emits;
+/// `${BASE_URL}` in template strings is dropped, leaving the site path.
+fn parse_ts_consts(src: &str) -> Result<HashMap<String, Value>> {
+    let mut consts = HashMap::new();
+    let mut rest = src;
+    while let Some(start) = rest.find("export const ") {
+        rest = &rest[start + "export const ".len()..];
+        let name_end = rest
+            .find(|c: char| !c.is_alphanumeric() && c != '_')
+            .unwrap_or(rest.len());
+        let name = rest[..name_end].to_string();
+        let eq = rest.find('=').context("export const without a value")?;
+        let mut parser = Literal {
+            chars: rest[eq + 1..].chars().collect(),
+            pos: 0,
+        };
+        let value = parser
+            .value()
+            .with_context(|| format!("Invalid literal for '{}'", name))?;
+        consts.insert(name, value);
+        let consumed: usize = parser.chars[..parser.pos]
+            .iter()
+            .map(|c| c.len_utf8())
+            .sum();
+        rest = &rest[eq + 1 + consumed..];
+    }
+    Ok(consts)
+}
+
+/// Recursive-descent reader for TS object literals
+struct Literal {
+    chars: Vec<char>,
+    pos: usize,
+}
+
+impl Literal {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    /// Skip whitespace and `//` comments
+    fn skip(&mut self) {
+        while let Some(c) = self.peek() {
+            if c.is_whitespace() {
+                self.pos += 1;
+            } else if c == '/' && self.chars.get(self.pos + 1) == Some(&'/') {
+                while self.peek().is_some_and(|c| c != '\n') {
+                    self.pos += 1;
+                }
+            } else {
+                break;
+            }
+        }
+    }
+
+    fn expect(&mut self, want: char) -> Result<()> {
+        self.skip();
+        anyhow::ensure!(
+            self.peek() == Some(want),
+            "expected '{}' at offset {}, found {:?}",
+            want,
+            self.pos,
+            self.peek()
+        );
+        self.pos += 1;
+        Ok(())
+    }
+
+    fn value(&mut self) -> Result<Value> {
+        self.skip();
+        match self.peek() {
+            Some('{') => self.object(),
+            Some('[') => self.array(),
+            Some(q @ ('\'' | '"' | '`')) => self.string(q).map(|s| {
+                let s = if q == '`' {
+                    s.replace("${BASE_URL}", "")
+                } else {
+                    s
+                };
+                Value::String(s)
+            }),
+            Some(c) if c == '-' || c.is_ascii_digit() => self.number(),
+            Some(c) if c.is_alphabetic() => match self.ident().as_str() {
+                "true" => Ok(Value::Bool(true)),
+                "false" => Ok(Value::Bool(false)),
+                "null" | "undefined" => Ok(Value::Null),
+                other => anyhow::bail!("unsupported expression '{}' at offset {}", other, self.pos),
+            },
+            other => anyhow::bail!("unexpected {:?} at offset {}", other, self.pos),
+        }
+    }
+
+    fn object(&mut self) -> Result<Value> {
+        self.expect('{')?;
+        let mut obj = serde_json::Map::new();
+        loop {
+            self.skip();
+            match self.peek() {
+                Some('}') => break,
+                Some(q @ ('\'' | '"')) => {
+                    let key = self.string(q)?;
+                    self.expect(':')?;
+                    obj.insert(key, self.value()?);
+                }
+                _ => {
+                    let key = self.ident();
+                    anyhow::ensure!(!key.is_empty(), "expected a key at offset {}", self.pos);
+                    self.expect(':')?;
+                    obj.insert(key, self.value()?);
+                }
+            }
+            self.skip();
+            if self.peek() == Some(',') {
+                self.pos += 1;
+            }
+        }
+        self.pos += 1;
+        Ok(Value::Object(obj))
+    }
+
+    fn array(&mut self) -> Result<Value> {
+        self.expect('[')?;
+        let mut items = Vec::new();
+        loop {
+            self.skip();
+            if self.peek() == Some(']') {
+                break;
+            }
+            items.push(self.value()?);
+            self.skip();
+            if self.peek() == Some(',') {
+                self.pos += 1;
+            }
+        }
+        self.pos += 1;
+        Ok(Value::Array(items))
+    }
+
+    fn string(&mut self, quote: char) -> Result<String> {
+        self.pos += 1;
+        let mut out = String::new();
+        loop {
+            let c = self.peek().context("unterminated string")?;
+            self.pos += 1;
+            match c {
+                '\\' => {
+                    let escaped = self.peek().context("unterminated string")?;
+                    self.pos += 1;
+                    out.push(match escaped {
+                        'n' => '\n',
+                        't' => '\t',
+                        other => other,
+                    });
+                }
+                c if c == quote => return Ok(out),
+                c => out.push(c),
+            }
+        }
+    }
+
+    fn number(&mut self) -> Result<Value> {
+        let start = self.pos;
+        while self
+            .peek()
+            .is_some_and(|c| c == '-' || c == '.' || c.is_ascii_digit())
+        {
+            self.pos += 1;
+        }
+        let text: String = self.chars[start..self.pos].iter().collect();
+        let n: f64 = text
+            .parse()
+            .with_context(|| format!("invalid number '{}'", text))?;
+        Ok(if n.fract() == 0.0 {
+            json!(n as i64)
+        } else {
+            json!(n)
+        })
+    }
+
+    fn ident(&mut self) -> String {
+        let start = self.pos;
+        while self.peek().is_some_and(|c| c.is_alphanumeric() || c == '_') {
+            self.pos += 1;
+        }
+        self.chars[start..self.pos].iter().collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::output::ts_writer::generate_mock_data_ts;
+    use std::collections::BTreeMap;
+
+    #[test]
+    fn reads_back_generated_mock_data() {
+        let data = MockData {
+            store_info: StoreInfo {
+                name: "伟盛酒业".to_string(),
+                phone: "15936229925".to_string(),
+                qr_code_url: "images/qrcode.jpg".to_string(),
+                qr_file_token: None,
+            },
+            media_playlist: vec![MediaItem {
+                media_type: "image".to_string(),
+                url: "images/media/女儿红.png".to_string(),
+                title: Some("女儿红 'wedding'".to_string()),
+                duration: Some(8000),
+                sort_order: 1,
+            }],
+            categories: vec![DisplayCategory {
+                id: "hot".to_string(),
+                name: "热销".to_string(),
+                icon: Some("🔥".to_string()),
+                sort_order: 1,
+            }],
+            products: vec![MockProduct {
+                id: "P001".to_string(),
+                name: "花雕".to_string(),
+                description: "五年陈\n绍兴".to_string(),
+                price: 128.5,
+                image: "https://example.com/a.png".to_string(),
+                category_id: "hot".to_string(),
+            }],
+            slogans: vec![Slogan {
+                text: "🎉 欢迎光临".to_string(),
+                sort_order: 1,
+            }],
+        };
+        let ts = generate_mock_data_ts(&data).unwrap();
+        let parsed = parse_mock_data(&ts).unwrap();
+
+        assert_eq!(parsed.store_info.name, "伟盛酒业");
+        assert_eq!(parsed.store_info.qr_code_url, "images/qrcode.jpg");
+        let media = &parsed.media_playlist[0];
+        assert_eq!(media.url, "images/media/女儿红.png");
+        assert_eq!(media.title.as_deref(), Some("女儿红 'wedding'"));
+        assert_eq!(media.duration, Some(8000));
+        assert_eq!(parsed.categories[0].icon.as_deref(), Some("🔥"));
+        let product = &parsed.products[0];
+        assert_eq!(product.description, "五年陈\n绍兴");
+        assert_eq!(product.price, 128.5);
+        assert_eq!(product.image, "https://example.com/a.png");
+        assert_eq!(product.category_id, "hot");
+        assert_eq!(parsed.slogans[0].text, "🎉 欢迎光临");
+    }
+
+    #[test]
+    fn product_rows_link_brand_and_category_records() {
+        let db: ProductDatabase = serde_json::from_value(json!({
+            "version": "1.0.0",
+            "lastUpdated": "2024-01-01",
+            "brands": [],
+            "categories": [],
+            "suppliers": [],
+            "products": [{
+                "id": "P001", "sku": "GYL-5", "barcode": "", "name": "五年花雕",
+                "brand": {"id": "1", "name": "古越龙山"},
+                "category": {"id": "huadiao", "name": "花雕", "level": 1},
+                "specification": "500ml", "unit": "瓶", "packSize": 1, "weight": 500,
+                "retailPrice": 68.0, "stock": 10, "safetyStock": 2, "origin": "浙江绍兴",
+                "shelfLife": 36, "storageCondition": "", "alcoholContent": 14.0,
+                "brewingProcess": "", "flavorProfile": "", "mainImage": "",
+                "shortDescription": "", "status": "active", "isHot": true,
+                "isNew": false, "isPromotion": false,
+                "createdAt": "2024-03-01T00:00:00Z", "updatedAt": "2024-03-01T00:00:00Z"
+            }]
+        }))
+        .unwrap();
+        let mock_products = vec![MockProduct {
+            id: "P001".to_string(),
+            name: String::new(),
+            description: String::new(),
+            price: 0.0,
+            image: String::new(),
+            category_id: "hot".to_string(),
+        }];
+        let brands = HashMap::from([("1".to_string(), "recB".to_string())]);
+        let categories = HashMap::from([("huadiao".to_string(), "recC".to_string())]);
+
+        let row = product_row(&db.products[0], 0, &mock_products, &brands, &categories);
+        let f = &row.fields;
+        assert_eq!(f["id"], "P001");
+        assert_eq!(f["retail_price"], 68.0);
+        assert_eq!(f["is_hot"], true);
+        assert_eq!(f["display_category_ids"], "hot");
+        assert_eq!(f["brand"], json!(["recB"]));
+        assert_eq!(f["category"], json!(["recC"]));
+        assert_eq!(f["listed_at"], 1709251200000i64);
+        // Empty values are left out
+        assert!(!f.contains_key("main_image") && !f.contains_key("brewing_process"));
+    }
+
+    #[test]
+    fn rows_are_written_to_aliased_and_overridden_columns() {
+        let mut row = Row::new(TableKind::Products);
+        row.set("id", "P001");
+        row.set("retail_price", 68.0);
+        row.set("is_hot", true);
+        let available: Vec<String> = ["Product ID", "售价"].map(String::from).to_vec();
+        let overrides = BTreeMap::from([("retail_price".to_string(), "售价".to_string())]);
+        let cols = ColumnMap::resolve(TableKind::Products, &overrides, &available);
+
+        let (records, dropped) = to_columns(vec![row], &cols, &available);
+        assert_eq!(records[0]["Product ID"], "P001");
+        assert_eq!(records[0]["售价"], 68.0);
+        assert_eq!(records[0].len(), 2);
+        assert_eq!(dropped, vec!["热销"]);
+    }
+}
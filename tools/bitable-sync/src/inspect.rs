@@ -0,0 +1,228 @@
+//! `config show` and `config validate`: what the settings resolve to and
+//! where each value came from, without touching the network.
+
+use anyhow::{Context, Result};
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use crate::config::{column_var, detect_repo_root, Config, TableKind, DEFAULT_FEISHU_BASE_URL};
+use crate::profile::{Settings, Source};
+use crate::schema::table_spec;
+use crate::secret::redact;
+
+/// Where a resolved setting came from
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Origin {
+    Set(Source),
+    Default,
+    /// Auto-detected, and how
+    Detected(&'static str),
+    Unset,
+}
+
+impl fmt::Display for Origin {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Origin::Set(source) => write!(f, "{}", source),
+            Origin::Default => write!(f, "default"),
+            Origin::Detected(how) => write!(f, "auto-detected: {}", how),
+            Origin::Unset => write!(f, "not set"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Resolved {
+    pub key: String,
+    pub value: String,
+    pub origin: Origin,
+}
+
+/// Every setting `Config::load` reads, with secrets redacted
+pub fn resolve_settings(settings: &Settings) -> Vec<Resolved> {
+    let mut out = Vec::new();
+    let mut add = |key: &str, default: Option<(String, Origin)>| {
+        let (value, origin) = match (settings.lookup(key), default) {
+            (Some((value, source)), _) => {
+                (redact(key, value).to_string(), Origin::Set(source.clone()))
+            }
+            (None, Some(default)) => default,
+            (None, None) => (String::new(), Origin::Unset),
+        };
+        out.push(Resolved {
+            key: key.to_string(),
+            value,
+            origin,
+        });
+    };
+    let path = |p: PathBuf| p.display().to_string();
+
+    add(
+        "FEISHU_BASE_URL",
+        Some((DEFAULT_FEISHU_BASE_URL.to_string(), Origin::Default)),
+    );
+    add("FEISHU_APP_ID", None);
+    // Only the key the secret is actually taken from
+    let secret_keys = [
+        "FEISHU_APP_SECRET",
+        "FEISHU_APP_SECRET_FILE",
+        "FEISHU_APP_SECRET_COMMAND",
+    ];
+    let secret_key = settings
+        .first_of(&secret_keys)
+        .map_or(secret_keys[0], |(k, _)| k);
+    add(secret_key, None);
+    add("BITABLE_APP_TOKEN", None);
+
+    let (repo_root, origin) = match settings.get("FAMILY_BUSINESS_REPO") {
+        Some(repo) => (PathBuf::from(repo), None),
+        None => {
+            let (root, how) = detect_repo_root();
+            (root.clone(), Some((path(root), Origin::Detected(how))))
+        }
+    };
+    add("FAMILY_BUSINESS_REPO", origin);
+    add(
+        "SYNC_STATE_DIR",
+        Some((
+            path(repo_root.join("tools/bitable-sync/.sync-state")),
+            Origin::Default,
+        )),
+    );
+    add(
+        "BACKUP_DIR",
+        Some((
+            path(repo_root.join("tools/bitable-sync/backups")),
+            Origin::Default,
+        )),
+    );
+    add("SYNC_PUSH", Some(("true".to_string(), Origin::Default)));
+
+    for kind in TableKind::ALL {
+        let suffix = kind.env_suffix();
+        add(&format!("TABLE_ID_{}", suffix), None);
+        add(
+            &format!("TABLE_NAME_{}", suffix),
+            Some((kind.default_name().to_string(), Origin::Default)),
+        );
+        add(&format!("VIEW_{}", suffix), None);
+        // Column overrides only when set; the defaults are in schema.toml
+        for model in table_spec(kind).fields.iter().filter_map(|f| f.model.as_deref()) {
+            let key = column_var(kind, model);
+            if settings.get(&key).is_some() {
+                add(&key, None);
+            }
+        }
+    }
+    out
+}
+
+pub fn show_config(settings: &Settings) {
+    let sources: Vec<String> = settings.sources().map(|s| s.to_string()).collect();
+    println!("Settings read from: {}\n", sources.join(", "));
+
+    let resolved = resolve_settings(settings);
+    let key_width = resolved.iter().map(|r| r.key.len()).max().unwrap_or(0);
+    for r in &resolved {
+        let value = if r.value.is_empty() { "-" } else { &r.value };
+        println!("{:key_width$}  {}  ({})", r.key, value, r.origin);
+    }
+}
+
+/// Load the config and run the path checks `sync` would, offline
+pub fn validate_config(settings: Settings) -> Result<()> {
+    let config = Config::load(settings).context("Config is invalid")?;
+    config.validate().context("Config validation failed")?;
+    println!("Repository path OK: {}", config.repo_root.display());
+
+    for (what, dir) in [
+        ("SYNC_STATE_DIR", &config.state_dir),
+        ("BACKUP_DIR", &config.backup_dir),
+    ] {
+        check_dir(what, dir)?;
+    }
+    if let Some(node) = &config.wiki_node {
+        println!(
+            "App is a wiki link ({}); resolved when a command runs",
+            node
+        );
+    }
+    for (kind, table) in &config.tables {
+        if table.id.is_empty() {
+            println!(
+                "{:?}: no TABLE_ID_{}, will look up '{}' by name",
+                kind,
+                kind.env_suffix(),
+                table.name.as_deref().unwrap_or(kind.default_name())
+            );
+        }
+    }
+    println!("\nConfig OK. Run `bitable-sync check` to test auth and the tables.");
+    Ok(())
+}
+
+/// `dir` is a directory, or can be created in one
+fn check_dir(what: &str, dir: &Path) -> Result<()> {
+    if dir.exists() {
+        anyhow::ensure!(
+            dir.is_dir(),
+            "{} is not a directory: {}",
+            what,
+            dir.display()
+        );
+        println!("{} OK: {}", what, dir.display());
+        return Ok(());
+    }
+    let parent = dir.ancestors().skip(1).find(|p| p.exists());
+    anyhow::ensure!(
+        parent.is_some_and(Path::is_dir),
+        "{} can't be created: {}",
+        what,
+        dir.display()
+    );
+    println!("{} will be created: {}", what, dir.display());
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeMap;
+
+    #[test]
+    fn reports_sources_defaults_and_redacts_secrets() {
+        let vars = |pairs: &[(&str, &str)]| -> BTreeMap<String, String> {
+            pairs
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect()
+        };
+        let env_file = Source::EnvFile(PathBuf::from(".env.txt"));
+        let settings = Settings::from_layers(vec![
+            (Source::Env, vars(&[("FEISHU_APP_ID", "cli_env")])),
+            (
+                env_file.clone(),
+                vars(&[
+                    ("FEISHU_APP_ID", "cli_file"),
+                    ("FEISHU_APP_SECRET", "hunter2"),
+                    ("FAMILY_BUSINESS_REPO", "/srv/site"),
+                ]),
+            ),
+        ]);
+
+        let resolved = resolve_settings(&settings);
+        let get = |key: &str| resolved.iter().find(|r| r.key == key).unwrap();
+
+        assert_eq!(get("FEISHU_APP_ID").value, "cli_env");
+        assert_eq!(get("FEISHU_APP_ID").origin, Origin::Set(Source::Env));
+        assert_eq!(get("FEISHU_APP_SECRET").value, "[redacted]");
+        assert_eq!(get("FEISHU_APP_SECRET").origin, Origin::Set(env_file));
+        assert_eq!(get("FEISHU_BASE_URL").origin, Origin::Default);
+        assert_eq!(
+            get("SYNC_STATE_DIR").value,
+            "/srv/site/tools/bitable-sync/.sync-state"
+        );
+        assert_eq!(get("BITABLE_APP_TOKEN").origin, Origin::Unset);
+        assert!(!resolved.iter().any(|r| r.value.contains("hunter2")));
+    }
+}
@@ -1,19 +1,42 @@
+mod backup;
+mod columns;
 mod config;
 mod feishu;
 mod git;
+mod import;
+mod inspect;
+mod migrate;
 mod models;
 mod output;
+mod profile;
+mod prompt;
+mod schema;
+mod secret;
 mod setup;
+mod snapshot;
 mod sync;
+#[cfg(test)]
+mod testing;
 mod transform;
+mod upload;
+mod validate;
 mod video;
 
 use clap::{Parser, Subcommand};
+use std::path::PathBuf;
 
 #[derive(Parser)]
 #[command(name = "bitable-sync")]
 #[command(about = "Sync product data from Feishu Bitable to family-business website")]
 struct Cli {
+    /// Profile from the config file to use (default: its `default_profile`)
+    #[arg(long, global = true)]
+    profile: Option<String>,
+
+    /// Config file with profiles (default: bitable-sync.toml if present)
+    #[arg(long, global = true)]
+    config: Option<PathBuf>,
+
     #[command(subcommand)]
     command: Commands,
 }
@@ -29,6 +52,15 @@ enum Commands {
         /// Write files but don't git commit/push
         #[arg(long)]
         no_push: bool,
+
+        /// Re-read all tables instead of only records changed since the last sync
+        #[arg(long)]
+        full: bool,
+
+        /// Abort before writing any file when validation finds errors
+        /// (broken links, duplicate ids, missing prices...)
+        #[arg(long)]
+        strict: bool,
     },
 
     /// List all tables in the bitable app (for configuration)
@@ -37,11 +69,89 @@ enum Commands {
     /// Verify configuration and connectivity
     Check,
 
-    /// Create all tables in bitable from scratch (destructive!)
-    Setup,
+    /// Create all tables in bitable from scratch, deleting the existing ones
+    /// (destructive!)
+    Setup {
+        /// Only print the tables that would be created and deleted
+        #[arg(long)]
+        dry_run: bool,
+
+        /// Delete the existing tables without asking
+        #[arg(long)]
+        yes: bool,
+
+        /// Write the new TABLE_ID_* values into .env.txt (other lines are kept)
+        #[arg(long)]
+        write_env: bool,
+    },
 
-    /// Add the slogans table to an existing bitable app (non-destructive)
+    /// Add the slogans table to an existing bitable app (non-destructive).
+    /// `migrate` covers this and any other missing table or field.
     AddSlogansTable,
+
+    /// Add missing tables, fields, links and select options to an existing
+    /// bitable app without touching data
+    Migrate {
+        /// Apply the plan without asking for confirmation
+        #[arg(long)]
+        yes: bool,
+    },
+
+    /// Fill a freshly set-up bitable app from productDatabase.json and
+    /// mockData.ts, uploading the images they reference
+    Import {
+        /// Only print what would be written
+        #[arg(long)]
+        dry_run: bool,
+    },
+
+    /// Save every table's fields, records and attachment files to a local
+    /// backup directory
+    Backup {
+        /// Directory to write (default: tools/bitable-sync/backups/<timestamp>)
+        #[arg(long)]
+        out: Option<PathBuf>,
+    },
+
+    /// Recreate the tables and records of a backup in the bitable app
+    Restore {
+        /// Backup directory (the one holding manifest.json)
+        dir: PathBuf,
+
+        /// Restore without asking for confirmation
+        #[arg(long)]
+        yes: bool,
+
+        /// Delete existing tables with the same names after restoring
+        #[arg(long)]
+        replace: bool,
+    },
+
+    /// Show or validate the resolved configuration (no network access)
+    Config {
+        #[command(subcommand)]
+        action: ConfigAction,
+    },
+
+    /// Upload images in public/images/products to the 商品主图 field
+    UploadImages {
+        /// Only list the images that would be uploaded
+        #[arg(long)]
+        dry_run: bool,
+
+        /// Replace images already set in bitable
+        #[arg(long)]
+        overwrite: bool,
+    },
+}
+
+#[derive(Subcommand)]
+enum ConfigAction {
+    /// Print every setting (secrets redacted) and where it came from
+    Show,
+
+    /// Load the config and check the repo and output paths
+    Validate,
 }
 
 #[tokio::main]
@@ -50,26 +160,88 @@ async fn main() -> anyhow::Result<()> {
 
     let cli = Cli::parse();
 
-    let config = config::Config::load()?;
+    let settings = profile::Settings::load(cli.profile.as_deref(), cli.config.as_deref())?;
+    // These work on a config that doesn't load, and without the network
+    let command = match cli.command {
+        Commands::Config { action } => {
+            return match action {
+                ConfigAction::Show => {
+                    inspect::show_config(&settings);
+                    Ok(())
+                }
+                ConfigAction::Validate => inspect::validate_config(settings),
+            };
+        }
+        command => command,
+    };
+
+    let mut config = config::Config::load(settings)?;
+    config.resolve().await?;
 
-    match cli.command {
-        Commands::Sync { dry_run, no_push } => {
+    match command {
+        Commands::Sync {
+            dry_run,
+            no_push,
+            full,
+            strict,
+        } => {
             config.validate()?;
-            let opts = sync::SyncOptions { dry_run, no_push };
+            config.resolve_tables().await?;
+            let opts = sync::SyncOptions {
+                dry_run,
+                no_push,
+                full,
+                strict,
+            };
             sync::run_sync(&config, &opts).await?;
         }
         Commands::ListTables => {
             sync::list_tables(&config).await?;
         }
         Commands::Check => {
+            config.resolve_tables().await?;
             sync::check_config(&config).await?;
         }
-        Commands::Setup => {
-            setup::setup_tables(&config).await?;
+        Commands::Setup {
+            dry_run,
+            yes,
+            write_env,
+        } => {
+            let opts = setup::SetupOptions {
+                dry_run,
+                yes,
+                write_env,
+            };
+            setup::setup_tables(&config, &opts).await?;
         }
         Commands::AddSlogansTable => {
             setup::create_slogans_table(&config).await?;
         }
+        Commands::Migrate { yes } => {
+            let opts = migrate::MigrateOptions { yes };
+            migrate::migrate(&mut config, &opts).await?;
+        }
+        Commands::Import { dry_run } => {
+            config.validate()?;
+            config.resolve_tables().await?;
+            let opts = import::ImportOptions { dry_run };
+            import::import(&config, &opts).await?;
+        }
+        Commands::Backup { out } => {
+            let opts = backup::BackupOptions { out };
+            backup::backup(&config, &opts).await?;
+        }
+        Commands::Restore { dir, yes, replace } => {
+            let opts = backup::RestoreOptions { dir, yes, replace };
+            backup::restore(&config, &opts).await?;
+        }
+        Commands::UploadImages { dry_run, overwrite } => {
+            config.validate()?;
+            config.resolve_tables().await?;
+            let opts = upload::UploadOptions { dry_run, overwrite };
+            upload::upload_product_images(&config, &opts).await?;
+        }
+        Commands::Config { .. } => unreachable!("handled before loading the config"),
     }
 
     Ok(())
@@ -0,0 +1,533 @@
+//! Non-destructive schema migration: bring an existing base up to
+//! `define_schemas()` by adding tables, fields, links and select options.
+//! Nothing is renamed, retyped or deleted; such differences are reported
+//! for a manual fix.
+
+use anyhow::{Context, Result};
+use std::collections::{BTreeMap, HashSet};
+use std::fmt;
+
+use crate::columns::ColumnMap;
+use crate::config::{Config, TableKind};
+use crate::feishu::auth::FeishuAuth;
+use crate::feishu::bitable::{BitableClient, FieldDef, FieldInfo};
+use crate::prompt::confirm;
+use crate::schema::{define_schemas, diff_table, select_options, Severity, TableSchema};
+
+pub struct MigrateOptions {
+    /// Apply without asking for confirmation
+    pub yes: bool,
+}
+
+/// One change to the base
+#[derive(Debug)]
+enum Step {
+    /// Create a table with its plain fields (links and lookups follow as separate steps)
+    CreateTable {
+        kind: TableKind,
+        name: String,
+    },
+    AddField {
+        kind: TableKind,
+        field: FieldDef,
+    },
+    /// Update a select field with its existing options plus `added`
+    ExtendOptions {
+        kind: TableKind,
+        field_id: String,
+        field: FieldDef,
+        added: Vec<String>,
+    },
+    AddLink {
+        kind: TableKind,
+        name: &'static str,
+        target: TableKind,
+    },
+    AddLookup {
+        kind: TableKind,
+        name: &'static str,
+        target: TableKind,
+        target_field: &'static str,
+    },
+}
+
+impl fmt::Display for Step {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Step::CreateTable { kind, name } => write!(f, "create table '{}' for {:?}", name, kind),
+            Step::AddField { kind, field } => write!(
+                f,
+                "{:?}: add field '{}' ({})",
+                kind,
+                field.field_name,
+                crate::feishu::bitable::FieldType::describe(field.field_type)
+            ),
+            Step::ExtendOptions {
+                kind, field, added, ..
+            } => write!(
+                f,
+                "{:?}: add options to '{}': {}",
+                kind,
+                field.field_name,
+                added.join(", ")
+            ),
+            Step::AddLink { kind, name, target } => {
+                write!(f, "{:?}: add link '{}' -> {:?}", kind, name, target)
+            }
+            Step::AddLookup {
+                kind,
+                name,
+                target,
+                target_field,
+            } => write!(
+                f,
+                "{:?}: add lookup '{}' of {:?}.{}",
+                kind, name, target, target_field
+            ),
+        }
+    }
+}
+
+/// Diff the live base against the schemas, print the plan and apply it on confirmation
+pub async fn migrate(config: &mut Config, opts: &MigrateOptions) -> Result<()> {
+    config.resolve_existing_tables().await?;
+    let auth = FeishuAuth::from_config(config);
+    let client = BitableClient::new(auth, config.bitable_app_token.clone());
+    let schemas = define_schemas();
+
+    let mut table_ids: BTreeMap<TableKind, String> = config
+        .tables
+        .iter()
+        .filter(|(_, t)| !t.id.is_empty())
+        .map(|(kind, t)| (*kind, t.id.clone()))
+        .collect();
+
+    let mut steps = Vec::new();
+    let mut manual = Vec::new();
+    for schema in &schemas {
+        let Some(kind) = schema.kind else { continue };
+        match table_ids.get(&kind) {
+            None => {
+                let name = config
+                    .table(kind)
+                    .name
+                    .clone()
+                    .unwrap_or_else(|| schema.name.to_string());
+                steps.push(Step::CreateTable { kind, name });
+                let cols = ColumnMap::for_fields(config, kind, &[]);
+                plan_table(kind, schema, &schemas, &[], &cols, &mut steps);
+            }
+            Some(table_id) => {
+                let fields = client.list_fields(table_id).await?;
+                let cols = ColumnMap::for_fields(config, kind, &fields);
+                plan_table(kind, schema, &schemas, &fields, &cols, &mut steps);
+                // Whatever adding can't fix (wrong types, wrong link targets)
+                for f in diff_table(schema, &schemas, &fields, &cols, &table_ids) {
+                    let exists = fields.iter().any(|have| have.field_name == f.field);
+                    if f.severity == Severity::Error && exists {
+                        manual.push(format!("{:?}: '{}' {}", kind, f.field, f.message));
+                    }
+                }
+            }
+        }
+    }
+
+    for m in &manual {
+        println!("Needs a manual fix: {}", m);
+    }
+    if steps.is_empty() {
+        println!("Base is up to date with the schema, nothing to migrate.");
+        return Ok(());
+    }
+
+    println!("\nMigration plan ({} changes):", steps.len());
+    for (i, step) in steps.iter().enumerate() {
+        println!("  {:>2}. {}", i + 1, step);
+    }
+    if !opts.yes && !confirm("\nApply these changes?")? {
+        println!("Aborted, nothing changed.");
+        return Ok(());
+    }
+
+    let skipped = apply_all(&client, &schemas, &steps, &mut table_ids).await?;
+
+    let created: Vec<_> = steps
+        .iter()
+        .filter_map(|s| match s {
+            Step::CreateTable { kind, .. } => Some(*kind),
+            _ => None,
+        })
+        .collect();
+    if !created.is_empty() {
+        println!("\nNew tables (optional in .env.txt, they are also found by name):");
+        for kind in created {
+            println!("TABLE_ID_{}={}", kind.env_suffix(), table_ids[&kind]);
+        }
+    }
+    if skipped.is_empty() {
+        println!("\nMigration complete.");
+    } else {
+        println!(
+            "\nMigration complete: {} applied, {} skipped (add them by hand in Feishu):",
+            steps.len() - skipped.len(),
+            skipped.len()
+        );
+        for step in skipped {
+            println!("  {}", step);
+        }
+    }
+    Ok(())
+}
+
+/// Add the steps that bring `actual` (empty for a table about to be created) up to `schema`.
+/// A field counts as present under the column `cols` reads it from, so
+/// renamed and English columns aren't added again.
+fn plan_table(
+    kind: TableKind,
+    schema: &TableSchema,
+    schemas: &[TableSchema],
+    actual: &[FieldInfo],
+    cols: &ColumnMap,
+    steps: &mut Vec<Step>,
+) {
+    let creating = actual.is_empty();
+    let find = |name: &str| {
+        let column = cols.column_for(name);
+        actual.iter().find(|f| f.field_name == column)
+    };
+
+    if !creating {
+        for want in &schema.fields {
+            let Some(have) = find(&want.field_name) else {
+                steps.push(Step::AddField {
+                    kind,
+                    field: want.clone(),
+                });
+                continue;
+            };
+            if have.field_type != want.field_type {
+                continue;
+            }
+            let existing: HashSet<String> =
+                select_options(have.property.as_ref()).into_iter().collect();
+            let added: Vec<String> = select_options(want.property.as_ref())
+                .into_iter()
+                .filter(|o| !existing.contains(o))
+                .collect();
+            if !added.is_empty() {
+                steps.push(Step::ExtendOptions {
+                    kind,
+                    field_id: have.field_id.clone(),
+                    field: with_options(want, have, &added),
+                    added,
+                });
+            }
+        }
+    }
+
+    for (name, index) in &schema.links {
+        if find(name).is_none() {
+            if let Some(target) = schemas[*index].kind {
+                steps.push(Step::AddLink { kind, name, target });
+            }
+        }
+    }
+    for (name, index, target_field) in &schema.lookups {
+        if find(name).is_none() {
+            if let Some(target) = schemas[*index].kind {
+                steps.push(Step::AddLookup {
+                    kind,
+                    name,
+                    target,
+                    target_field,
+                });
+            }
+        }
+    }
+}
+
+/// `want` under the live field's name, with its options (ids and colours
+/// kept) plus `added`
+fn with_options(want: &FieldDef, have: &FieldInfo, added: &[String]) -> FieldDef {
+    let mut options: Vec<serde_json::Value> = have
+        .property
+        .as_ref()
+        .and_then(|p| p.get("options"))
+        .and_then(|o| o.as_array())
+        .cloned()
+        .unwrap_or_default();
+    options.extend(added.iter().map(|name| serde_json::json!({"name": name})));
+
+    let mut property = have
+        .property
+        .clone()
+        .unwrap_or_else(|| serde_json::json!({}));
+    property["options"] = serde_json::Value::Array(options);
+    FieldDef {
+        field_name: have.field_name.clone(),
+        property: Some(property),
+        ..want.clone()
+    }
+}
+
+/// Apply `steps` in order, returning the ones skipped. A failed lookup is
+/// skipped: not every tenant allows creating lookups through the API, and
+/// sync falls back to the brand's 产地 without one (as in setup)
+async fn apply_all<'a>(
+    client: &BitableClient,
+    schemas: &[TableSchema],
+    steps: &'a [Step],
+    table_ids: &mut BTreeMap<TableKind, String>,
+) -> Result<Vec<&'a Step>> {
+    let mut skipped = Vec::new();
+    for step in steps {
+        match apply(client, schemas, step, table_ids).await {
+            Ok(()) => println!("  done: {}", step),
+            Err(e) if matches!(step, Step::AddLookup { .. }) => {
+                tracing::warn!("Could not {}: {:#}", step, e);
+                println!("  skipped: {}", step);
+                skipped.push(step);
+            }
+            Err(e) => return Err(e.context(format!("Failed to {}", step))),
+        }
+    }
+    Ok(skipped)
+}
+
+async fn apply(
+    client: &BitableClient,
+    schemas: &[TableSchema],
+    step: &Step,
+    table_ids: &mut BTreeMap<TableKind, String>,
+) -> Result<()> {
+    let id = |kind: &TableKind| -> Result<String> {
+        table_ids
+            .get(kind)
+            .cloned()
+            .with_context(|| format!("{:?} table does not exist", kind))
+    };
+
+    match step {
+        Step::CreateTable { kind, name } => {
+            let schema = schemas
+                .iter()
+                .find(|s| s.kind == Some(*kind))
+                .context("No schema for table")?;
+            let table_id = client
+                .create_table(name, schema.view_name, &schema.fields)
+                .await?;
+            table_ids.insert(*kind, table_id);
+        }
+        Step::AddField { kind, field } => {
+            client.create_field(&id(kind)?, field).await?;
+        }
+        Step::ExtendOptions {
+            kind,
+            field_id,
+            field,
+            ..
+        } => {
+            client.update_field(&id(kind)?, field_id, field).await?;
+        }
+        Step::AddLink { kind, name, target } => {
+            let field = FieldDef::link(name, &id(target)?);
+            client.create_field(&id(kind)?, &field).await?;
+        }
+        Step::AddLookup {
+            kind,
+            name,
+            target,
+            target_field,
+        } => {
+            let target_id = id(target)?;
+            let source = client
+                .list_fields(&target_id)
+                .await?
+                .into_iter()
+                .find(|f| f.field_name == *target_field)
+                .with_context(|| format!("Field '{}' not found in {:?}", target_field, target))?;
+            let field = FieldDef::lookup(name, &target_id, &source.field_id);
+            client.create_field(&id(kind)?, &field).await?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::feishu::bitable::FieldType;
+
+    fn field(name: &str, field_type: FieldType, property: Option<serde_json::Value>) -> FieldInfo {
+        FieldInfo {
+            field_id: format!("fld_{}", name),
+            field_name: name.to_string(),
+            field_type: field_type as i32,
+            ui_type: None,
+            is_primary: None,
+            property,
+        }
+    }
+
+    #[test]
+    fn plans_missing_fields_options_and_links() {
+        let schemas = define_schemas();
+        let media = schemas
+            .iter()
+            .find(|s| s.kind == Some(TableKind::Media))
+            .unwrap();
+        let actual = vec![
+            field("标题", FieldType::Text, None),
+            field(
+                "媒体类型",
+                FieldType::SingleSelect,
+                Some(serde_json::json!({"options": [{"id": "opt1", "name": "image", "color": 3}]})),
+            ),
+            field("文件", FieldType::Attachment, None),
+            // 排序 was turned into text by hand: not touched
+            field("排序", FieldType::Text, None),
+        ];
+
+        let mut steps = Vec::new();
+        let cols = ColumnMap::resolve(TableKind::Media, &Default::default(), &[]);
+        plan_table(TableKind::Media, media, &schemas, &actual, &cols, &mut steps);
+        let plan: Vec<String> = steps.iter().map(|s| s.to_string()).collect();
+
+        assert_eq!(
+            plan,
+            vec![
+                "Media: add options to '媒体类型': video",
+                "Media: add field '时长(ms)' (Number)",
+                "Media: add link '所属品牌' -> Brands",
+            ]
+        );
+        let Step::ExtendOptions { field, .. } = &steps[0] else {
+            panic!("expected options step");
+        };
+        assert_eq!(
+            field.property.as_ref().unwrap()["options"],
+            serde_json::json!([{"id": "opt1", "name": "image", "color": 3}, {"name": "video"}])
+        );
+    }
+
+    #[test]
+    fn renamed_columns_are_not_added_again() {
+        let schemas = define_schemas();
+        let products = schemas
+            .iter()
+            .find(|s| s.kind == Some(TableKind::Products))
+            .unwrap();
+        // As created, but with 零售价 renamed (override) and 单位 in English (alias)
+        let actual: Vec<FieldInfo> = products
+            .fields
+            .iter()
+            .map(|f| {
+                let (name, property) = match f.field_name.as_str() {
+                    "零售价" => ("售价", f.property.clone()),
+                    "单位" => ("Unit", Some(serde_json::json!({"options": [{"name": "瓶"}]}))),
+                    name => (name, f.property.clone()),
+                };
+                FieldInfo {
+                    field_type: f.field_type,
+                    ..field(name, FieldType::Text, property)
+                }
+            })
+            .collect();
+        let available: Vec<String> = actual.iter().map(|f| f.field_name.clone()).collect();
+        let overrides = BTreeMap::from([("retail_price".to_string(), "售价".to_string())]);
+        let cols = ColumnMap::resolve(TableKind::Products, &overrides, &available);
+
+        let mut steps = Vec::new();
+        plan_table(TableKind::Products, products, &schemas, &actual, &cols, &mut steps);
+
+        assert!(!steps.iter().any(|s| matches!(s, Step::AddField { .. })));
+        let Step::ExtendOptions { field, added, .. } = &steps[0] else {
+            panic!("expected options step");
+        };
+        // Options are added to the live column, which keeps its name
+        assert_eq!(field.field_name, "Unit");
+        assert_eq!(added, &vec!["箱", "坛", "盒"]);
+    }
+
+    #[test]
+    fn new_tables_get_their_links_after_creation() {
+        let schemas = define_schemas();
+        let products = schemas
+            .iter()
+            .find(|s| s.kind == Some(TableKind::Products))
+            .unwrap();
+
+        let mut steps = Vec::new();
+        let cols = ColumnMap::resolve(TableKind::Products, &Default::default(), &[]);
+        plan_table(TableKind::Products, products, &schemas, &[], &cols, &mut steps);
+        let plan: Vec<String> = steps.iter().map(|s| s.to_string()).collect();
+
+        assert_eq!(
+            plan,
+            vec![
+                "Products: add link '品牌' -> Brands",
+                "Products: add link '分类' -> DisplayCategories",
+                "Products: add lookup '产地' of Brands.产地",
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn failed_lookup_is_skipped_and_the_rest_applied() {
+        use crate::feishu::auth::FeishuAuth;
+        use crate::feishu::stub::{token_response, StubResponse, StubServer};
+
+        let server = StubServer::start(|req| {
+            if req.path.starts_with("/auth/") {
+                return token_response("t", 7200);
+            }
+            if req.method == "GET" {
+                return StubResponse::json(serde_json::json!({"code": 0, "msg": "ok", "data": {
+                    "has_more": false,
+                    "items": [{"field_id": "fldO", "field_name": "产地", "type": 1}]
+                }}));
+            }
+            if req.body.contains("\"type\":19") {
+                return StubResponse::json(
+                    serde_json::json!({"code": 1254045, "msg": "FieldTypeNotSupported"}),
+                );
+            }
+            StubResponse::json(serde_json::json!({"code": 0, "msg": "ok", "data": {
+                "field": {"field_id": "fldN", "field_name": "备注", "type": 1}
+            }}))
+        })
+        .await;
+
+        let auth = FeishuAuth::new(&server.base_url, "app".into(), "secret".into());
+        let client = BitableClient::new(auth, "app_token".into());
+        let steps = vec![
+            Step::AddLookup {
+                kind: TableKind::Products,
+                name: "产地",
+                target: TableKind::Brands,
+                target_field: "产地",
+            },
+            Step::AddField {
+                kind: TableKind::Products,
+                field: FieldDef::text("备注"),
+            },
+        ];
+        let mut table_ids = BTreeMap::from([
+            (TableKind::Products, "tblP".to_string()),
+            (TableKind::Brands, "tblB".to_string()),
+        ]);
+
+        let skipped = apply_all(&client, &define_schemas(), &steps, &mut table_ids)
+            .await
+            .unwrap();
+
+        assert_eq!(skipped.len(), 1);
+        assert!(matches!(skipped[0], Step::AddLookup { .. }));
+        let created = server
+            .requests()
+            .into_iter()
+            .filter(|r| r.method == "POST" && r.body.contains("备注"))
+            .count();
+        assert_eq!(created, 1);
+    }
+}
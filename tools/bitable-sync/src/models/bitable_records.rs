@@ -1,207 +1,56 @@
 use anyhow::{Context, Result};
 use std::collections::HashMap;
 
-/// Helper to extract a text field from bitable record fields.
-/// Bitable text fields come as either a plain string or an array of objects with "text" key.
-pub fn extract_text(fields: &HashMap<String, serde_json::Value>, key: &str) -> Option<String> {
-    let val = fields.get(key)?;
+use super::bitable_value::RecordFields;
+use crate::columns::ColumnMap;
 
-    // Plain string
-    if let Some(s) = val.as_str() {
-        return Some(s.to_string());
-    }
-
-    // Array of text segments: [{"text": "value", "type": "text"}]
-    if let Some(arr) = val.as_array() {
-        let parts: Vec<String> = arr
-            .iter()
-            .filter_map(|item| item.get("text").and_then(|t| t.as_str()))
-            .map(|s| s.to_string())
-            .collect();
-        if !parts.is_empty() {
-            return Some(parts.join(""));
-        }
-    }
-
-    // Number serialized as string
-    if let Some(n) = val.as_f64() {
-        return Some(n.to_string());
-    }
-
-    None
-}
-
-/// Extract a number field
-pub fn extract_number(fields: &HashMap<String, serde_json::Value>, key: &str) -> Option<f64> {
-    let val = fields.get(key)?;
-
-    if let Some(n) = val.as_f64() {
-        return Some(n);
-    }
-
-    // Sometimes numbers come as strings
-    if let Some(s) = val.as_str() {
-        return s.parse::<f64>().ok();
-    }
-
-    None
-}
-
-/// Extract a boolean/checkbox field
-pub fn extract_bool(fields: &HashMap<String, serde_json::Value>, key: &str) -> bool {
-    fields
-        .get(key)
-        .and_then(|v| v.as_bool())
-        .unwrap_or(false)
-}
-
-/// Extract a single-select field (returns the option text)
-pub fn extract_select(fields: &HashMap<String, serde_json::Value>, key: &str) -> Option<String> {
-    let val = fields.get(key)?;
-
-    // Single select can be a string directly or an object with "text" key
-    if let Some(s) = val.as_str() {
-        return Some(s.to_string());
-    }
-
-    // Or it might be an object like {"text": "value", "id": "..."}
-    if let Some(text) = val.get("text").and_then(|t| t.as_str()) {
-        return Some(text.to_string());
-    }
-
-    None
-}
-
-/// Extract a linked record field (returns the display text of the first link)
-pub fn extract_link_text(fields: &HashMap<String, serde_json::Value>, key: &str) -> Option<String> {
-    let val = fields.get(key)?;
-
-    // Link fields come as an array of linked record objects
-    if let Some(arr) = val.as_array() {
-        if let Some(first) = arr.first() {
-            // The linked record might have a "text" field or be a string
-            if let Some(text) = first.get("text").and_then(|t| t.as_str()) {
-                return Some(text.to_string());
-            }
-            if let Some(s) = first.as_str() {
-                return Some(s.to_string());
-            }
-        }
-    }
-
-    None
-}
-
-/// Extract a linked record ID (the record_id of the first linked record)
-#[allow(dead_code)]
-pub fn extract_link_record_id(
-    fields: &HashMap<String, serde_json::Value>,
-    key: &str,
-) -> Option<String> {
-    let val = fields.get(key)?;
-
-    if let Some(arr) = val.as_array() {
-        if let Some(first) = arr.first() {
-            if let Some(id) = first.get("record_id").and_then(|t| t.as_str()) {
-                return Some(id.to_string());
-            }
-            if let Some(s) = first.as_str() {
-                return Some(s.to_string());
-            }
-        }
-    }
-
-    None
-}
-
-/// Extract a phone field (type 13 returns plain string)
-pub fn extract_phone(fields: &HashMap<String, serde_json::Value>, key: &str) -> Option<String> {
-    extract_text(fields, key)
-}
-
-/// Extract a URL/hyperlink field (type 15)
-/// URL fields can come as {"text": "url", "link": "url"} or just a string
-#[allow(dead_code)]
-pub fn extract_url(fields: &HashMap<String, serde_json::Value>, key: &str) -> Option<String> {
-    let val = fields.get(key)?;
-
-    if let Some(s) = val.as_str() {
-        return Some(s.to_string());
-    }
-
-    // URL field as object
-    if let Some(link) = val.get("link").and_then(|l| l.as_str()) {
-        return Some(link.to_string());
-    }
-    if let Some(text) = val.get("text").and_then(|t| t.as_str()) {
-        return Some(text.to_string());
-    }
+// ============================================================
+// Field name mapping: columns come from `ColumnMap` (schema.toml
+// `model` keys, aliases and COLUMN_* overrides)
+// ============================================================
 
-    None
+/// Required text cell, naming the missing column in the error
+fn required_text(f: &RecordFields, what: &str, column: &str) -> Result<String> {
+    f.text(column)?
+        .with_context(|| format!("{} missing '{}'", what, column))
 }
 
-/// Extract attachment field (type 17) - returns the first file's URL
-/// Attachment fields come as an array of file objects with "url", "name", "tmp_url" etc.
-pub fn extract_attachment_url(
-    fields: &HashMap<String, serde_json::Value>,
-    key: &str,
-) -> Option<String> {
-    let val = fields.get(key)?;
-
-    if let Some(arr) = val.as_array() {
-        if let Some(first) = arr.first() {
-            // Try tmp_url first (temporary download URL), then url
-            if let Some(url) = first.get("tmp_url").and_then(|u| u.as_str()) {
-                return Some(url.to_string());
-            }
-            if let Some(url) = first.get("url").and_then(|u| u.as_str()) {
-                return Some(url.to_string());
-            }
-        }
-    }
-
-    None
+/// URL of the first attachment in a cell: tmp_url (temporary download URL) first, then url
+fn attachment_url(f: &RecordFields, key: &str) -> Result<Option<String>> {
+    Ok(f
+        .attachment(key)?
+        .and_then(|a| a.tmp_url.clone().or_else(|| a.url.clone())))
 }
 
-/// Extract the first attachment's file_token from an attachment field.
-/// Used for downloading the attachment via Feishu Drive API.
-pub fn extract_attachment_file_token(
-    fields: &HashMap<String, serde_json::Value>,
-    key: &str,
-) -> Option<String> {
-    let val = fields.get(key)?;
-    let arr = val.as_array()?;
-    let first = arr.first()?;
-    first.get("file_token").and_then(|t| t.as_str()).map(|s| s.to_string())
-}
-
-// ============================================================
-// Field name mapping: Chinese field names in bitable
-// ============================================================
-
 /// Parse a bitable record into a Brand
 pub fn parse_brand(
     fields: &HashMap<String, serde_json::Value>,
+    cols: &ColumnMap,
 ) -> Result<super::product::Brand> {
+    let f = RecordFields::new(fields);
+    let col = |model| cols.get(model);
     Ok(super::product::Brand {
-        id: extract_text(fields, "品牌ID").context("Brand missing '品牌ID'")?,
-        name: extract_text(fields, "品牌名称").context("Brand missing '品牌名称'")?,
-        logo: extract_attachment_url(fields, "品牌Logo"),
-        story: extract_text(fields, "品牌故事"),
-        founded_year: extract_number(fields, "创立年份").map(|n| n as i32),
-        origin: extract_text(fields, "产地"),
+        id: required_text(&f, "Brand", col("id"))?,
+        name: required_text(&f, "Brand", col("name"))?,
+        logo: attachment_url(&f, col("logo"))?,
+        story: f.text(col("story"))?,
+        founded_year: f.number(col("founded_year"))?.map(|n| n as i32),
+        origin: f.text(col("origin"))?,
     })
 }
 
 /// Parse a bitable record into a DisplayCategory
 pub fn parse_display_category(
     fields: &HashMap<String, serde_json::Value>,
+    cols: &ColumnMap,
 ) -> Result<super::mock_data::DisplayCategory> {
+    let f = RecordFields::new(fields);
+    let col = |model| cols.get(model);
     Ok(super::mock_data::DisplayCategory {
-        id: extract_text(fields, "分类ID").context("Category missing '分类ID'")?,
-        name: extract_text(fields, "分类名称").context("Category missing '分类名称'")?,
-        icon: extract_text(fields, "图标"),
-        sort_order: extract_number(fields, "排序").unwrap_or(0.0) as i32,
+        id: required_text(&f, "Category", col("id"))?,
+        name: required_text(&f, "Category", col("name"))?,
+        icon: f.text(col("icon"))?,
+        sort_order: f.number(col("sort_order"))?.unwrap_or(0.0) as i32,
     })
 }
 
@@ -209,43 +58,54 @@ pub fn parse_display_category(
 #[allow(dead_code)]
 pub fn parse_media_item(
     fields: &HashMap<String, serde_json::Value>,
+    cols: &ColumnMap,
 ) -> Result<super::mock_data::MediaItem> {
-    let url = extract_attachment_url(fields, "文件")
-        .context("Media missing '文件' attachment")?;
+    let f = RecordFields::new(fields);
+    let col = |model| cols.get(model);
+    let url = attachment_url(&f, col("file"))?
+        .with_context(|| format!("Media missing '{}' attachment", col("file")))?;
 
     Ok(super::mock_data::MediaItem {
-        media_type: extract_select(fields, "媒体类型").unwrap_or_else(|| "image".to_string()),
+        media_type: f.single_select(col("media_type"))?.unwrap_or_else(|| "image".to_string()),
         url,
-        title: extract_text(fields, "标题"),
-        duration: extract_number(fields, "时长(ms)").map(|n| n as i64),
-        sort_order: extract_number(fields, "排序").unwrap_or(0.0) as i32,
+        title: f.text(col("title"))?,
+        duration: f.number(col("duration"))?.map(|n| n as i64),
+        sort_order: f.number(col("sort_order"))?.unwrap_or(0.0) as i32,
     })
 }
 
 /// Parse a bitable record into StoreInfo
 pub fn parse_store_info(
     fields: &HashMap<String, serde_json::Value>,
+    cols: &ColumnMap,
 ) -> Result<super::mock_data::StoreInfo> {
+    let f = RecordFields::new(fields);
+    let col = |model| cols.get(model);
     Ok(super::mock_data::StoreInfo {
-        name: extract_text(fields, "店铺名称").context("StoreInfo missing '店铺名称'")?,
-        phone: extract_phone(fields, "联系电话").context("StoreInfo missing '联系电话'")?,
-        qr_code_url: extract_attachment_url(fields, "二维码").unwrap_or_default(),
-        qr_file_token: extract_attachment_file_token(fields, "二维码"),
+        name: required_text(&f, "StoreInfo", col("name"))?,
+        phone: f
+            .phone(col("phone"))?
+            .with_context(|| format!("StoreInfo missing '{}'", col("phone")))?,
+        qr_code_url: attachment_url(&f, col("qr_code"))?.unwrap_or_default(),
+        qr_file_token: f.attachment(col("qr_code"))?.map(|a| a.file_token.clone()),
     })
 }
 
 /// Parse a bitable record into a Slogan (ticker text)
 pub fn parse_slogan(
     fields: &HashMap<String, serde_json::Value>,
+    cols: &ColumnMap,
 ) -> Result<super::mock_data::Slogan> {
-    let text = extract_text(fields, "标语内容").context("Slogan missing '标语内容'")?;
-    let enabled = extract_bool(fields, "启用");
+    let f = RecordFields::new(fields);
+    let col = |model| cols.get(model);
+    let text = required_text(&f, "Slogan", col("text"))?;
+    let enabled = f.checkbox(col("enabled"))?;
     if !enabled {
         anyhow::bail!("Slogan disabled: {}", text);
     }
     Ok(super::mock_data::Slogan {
         text,
-        sort_order: extract_number(fields, "排序").unwrap_or(0.0) as i32,
+        sort_order: f.number(col("sort_order"))?.unwrap_or(0.0) as i32,
     })
 }
 
@@ -256,6 +116,10 @@ pub struct RawProduct {
     pub name: String,
     pub brand_id_link: Option<String>,
     pub category_id_link: Option<String>,
+    /// record_ids behind the links; search returns only these, without
+    /// display text, so sync resolves them against the brand and category reads
+    pub brand_record_id: Option<String>,
+    pub category_record_id: Option<String>,
     pub specification: String,
     pub unit: String,
     pub retail_price: f64,
@@ -277,49 +141,151 @@ pub struct RawProduct {
     pub is_promotion: bool,
     pub display_category_ids: Vec<String>,
     pub sort_order: i32,
+    /// Origin, usually a lookup of the linked brand's 产地
+    pub origin: Option<String>,
+    /// 上架日期 (date field)
+    pub listed_at: Option<chrono::DateTime<chrono::Utc>>,
+    /// 更新时间 (last-modified-time field)
+    pub updated_at: Option<chrono::DateTime<chrono::Utc>>,
 }
 
 /// Parse a bitable record into a RawProduct
 pub fn parse_raw_product(
     fields: &HashMap<String, serde_json::Value>,
+    cols: &ColumnMap,
 ) -> Result<RawProduct> {
-    let display_cats = extract_text(fields, "展示分类")
+    let f = RecordFields::new(fields);
+    let col = |model| cols.get(model);
+    // 展示分类 is either a multi-select or comma-separated text ("hot,huadiao")
+    let display_cats = f
+        .multi_select(col("display_category_ids"))?
         .unwrap_or_default()
-        .split(',')
+        .iter()
+        .flat_map(|s| s.split(','))
         .map(|s| s.trim().to_string())
         .filter(|s| !s.is_empty())
         .collect();
 
     // For main_image: try attachment URL first, fallback to empty
-    let main_image = extract_attachment_url(fields, "商品主图").unwrap_or_default();
-    let main_image_file_token = extract_attachment_file_token(fields, "商品主图");
+    let main_image = attachment_url(&f, col("main_image"))?.unwrap_or_default();
+    let main_image_file_token = f.attachment(col("main_image"))?.map(|a| a.file_token.clone());
 
     Ok(RawProduct {
-        id: extract_text(fields, "商品ID").context("Product missing '商品ID'")?,
-        sku: extract_text(fields, "商品编码").unwrap_or_default(),
-        name: extract_text(fields, "商品名称").context("Product missing '商品名称'")?,
-        brand_id_link: extract_link_text(fields, "品牌"),
-        category_id_link: extract_link_text(fields, "分类"),
-        specification: extract_text(fields, "规格").unwrap_or_default(),
-        unit: extract_select(fields, "单位").unwrap_or_else(|| "瓶".to_string()),
-        retail_price: extract_number(fields, "零售价").unwrap_or(0.0),
-        cost_price: extract_number(fields, "成本价"),
-        member_price: extract_number(fields, "会员价"),
-        promotion_price: extract_number(fields, "促销价"),
-        stock: extract_number(fields, "库存").unwrap_or(0.0) as i32,
-        alcohol_content: extract_number(fields, "酒精度%").unwrap_or(0.0),
-        vintage: extract_number(fields, "年份").map(|n| n as i32),
-        brewing_process: extract_text(fields, "酿造工艺").unwrap_or_default(),
-        flavor_profile: extract_text(fields, "风味描述").unwrap_or_default(),
+        id: required_text(&f, "Product", col("id"))?,
+        sku: f.text(col("sku"))?.unwrap_or_default(),
+        name: required_text(&f, "Product", col("name"))?,
+        brand_id_link: f.link_text(col("brand"))?,
+        category_id_link: f.link_text(col("category"))?,
+        brand_record_id: f.link_record_id(col("brand"))?,
+        category_record_id: f.link_record_id(col("category"))?,
+        specification: f.text(col("specification"))?.unwrap_or_default(),
+        unit: f.single_select(col("unit"))?.unwrap_or_else(|| "瓶".to_string()),
+        retail_price: f.number(col("retail_price"))?.unwrap_or(0.0),
+        cost_price: f.number(col("cost_price"))?,
+        member_price: f.number(col("member_price"))?,
+        promotion_price: f.number(col("promotion_price"))?,
+        stock: f.number(col("stock"))?.unwrap_or(0.0) as i32,
+        alcohol_content: f.number(col("alcohol_content"))?.unwrap_or(0.0),
+        vintage: f.number(col("vintage"))?.map(|n| n as i32),
+        brewing_process: f.text(col("brewing_process"))?.unwrap_or_default(),
+        flavor_profile: f.text(col("flavor_profile"))?.unwrap_or_default(),
         main_image,
         main_image_file_token,
-        short_description: extract_text(fields, "简短描述").unwrap_or_default(),
-        long_description: extract_text(fields, "详细描述"),
-        status: extract_select(fields, "状态").unwrap_or_else(|| "active".to_string()),
-        is_hot: extract_bool(fields, "热销"),
-        is_new: extract_bool(fields, "新品"),
-        is_promotion: extract_bool(fields, "促销中"),
+        short_description: f.text(col("short_description"))?.unwrap_or_default(),
+        long_description: f.text(col("long_description"))?,
+        status: f.single_select(col("status"))?.unwrap_or_else(|| "active".to_string()),
+        is_hot: f.checkbox(col("is_hot"))?,
+        is_new: f.checkbox(col("is_new"))?,
+        is_promotion: f.checkbox(col("is_promotion"))?,
         display_category_ids: display_cats,
-        sort_order: extract_number(fields, "排序").unwrap_or(0.0) as i32,
+        sort_order: f.number(col("sort_order"))?.unwrap_or(0.0) as i32,
+        origin: f.text(col("origin"))?,
+        listed_at: f.datetime(col("listed_at"))?,
+        updated_at: f.datetime(col("updated_at"))?,
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::TableKind;
+    use crate::schema::{table_spec, FieldKind};
+    use serde_json::json;
+
+    /// Columns as schema.toml names them
+    fn cols(kind: TableKind) -> ColumnMap {
+        ColumnMap::resolve(kind, &Default::default(), &[])
+    }
+
+    /// A record with every column of the table filled, as search returns it
+    fn full_record(kind: TableKind) -> HashMap<String, serde_json::Value> {
+        table_spec(kind)
+            .fields
+            .iter()
+            .filter(|f| f.kind != FieldKind::Formula)
+            .map(|f| {
+                let value = match f.kind {
+                    FieldKind::Number => json!(3),
+                    FieldKind::Checkbox => json!(true),
+                    FieldKind::SingleSelect => json!(f.options[0]),
+                    FieldKind::MultiSelect => json!(["hot"]),
+                    FieldKind::Datetime | FieldKind::CreatedTime | FieldKind::ModifiedTime => {
+                        json!(1700000000000i64)
+                    }
+                    FieldKind::Attachment => {
+                        json!([{"file_token": "box", "name": "a.png", "tmp_url": "https://t"}])
+                    }
+                    FieldKind::Link => json!([{"record_ids": ["rec1"], "text": "linked", "type": "text"}]),
+                    _ => json!(format!("{} value", f.name)),
+                };
+                (f.name.clone(), value)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn parses_full_records_of_every_table() {
+        let brand = parse_brand(&full_record(TableKind::Brands), &cols(TableKind::Brands)).unwrap();
+        assert_eq!(brand.id, "品牌ID value");
+        assert_eq!(brand.founded_year, Some(3));
+
+        parse_display_category(&full_record(TableKind::DisplayCategories), &cols(TableKind::DisplayCategories)).unwrap();
+        parse_media_item(&full_record(TableKind::Media), &cols(TableKind::Media)).unwrap();
+        crate::video::parse_raw_media_item(&full_record(TableKind::Media), &cols(TableKind::Media)).unwrap();
+        parse_store_info(&full_record(TableKind::StoreInfo), &cols(TableKind::StoreInfo)).unwrap();
+        parse_slogan(&full_record(TableKind::Slogans), &cols(TableKind::Slogans)).unwrap();
+
+        let product = parse_raw_product(&full_record(TableKind::Products), &cols(TableKind::Products)).unwrap();
+        assert_eq!(product.name, "商品名称 value");
+        assert_eq!(product.brand_id_link.as_deref(), Some("linked"));
+        assert_eq!(product.main_image, "https://t");
+        assert_eq!(product.status, "active");
+        assert!(product.listed_at.is_some());
+    }
+
+    #[test]
+    fn missing_required_column_is_named() {
+        let mut fields = full_record(TableKind::Products);
+        fields.remove("商品名称");
+        let err = parse_raw_product(&fields, &cols(TableKind::Products))
+            .err()
+            .unwrap();
+        assert_eq!(err.to_string(), "Product missing '商品名称'");
+    }
+
+    #[test]
+    fn english_columns_are_read_through_aliases() {
+        let fields: HashMap<String, serde_json::Value> = HashMap::from([
+            ("Product ID".to_string(), json!("P001")),
+            ("Product Name".to_string(), json!("Huadiao 5y")),
+            ("Price".to_string(), json!(88)),
+        ]);
+        let available: Vec<String> = fields.keys().cloned().collect();
+        let cols = ColumnMap::resolve(TableKind::Products, &Default::default(), &available);
+
+        let product = parse_raw_product(&fields, &cols).unwrap();
+        assert_eq!(product.id, "P001");
+        assert_eq!(product.name, "Huadiao 5y");
+        assert_eq!(product.retail_price, 88.0);
+    }
+}
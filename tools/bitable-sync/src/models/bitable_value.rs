@@ -0,0 +1,571 @@
+//! Typed model of Bitable cell values.
+//!
+//! Record JSON does not say which field type produced a value, so values are
+//! decoded by shape and the typed accessors accept every shape a field type
+//! can take. Shapes per field type:
+//!
+//! | Field type                          | Wire shape                               | Variant       |
+//! |-------------------------------------|------------------------------------------|---------------|
+//! | 多行文本 Text                        | `[{"type":"text","text":".."}]` or `".."`| `Text`        |
+//! | 单选 SingleSelect, 电话 Phone        | `".."`                                   | `Text`        |
+//! | 数字 Number                          | `12.5`                                   | `Number`      |
+//! | 日期 DateTime, 创建/修改时间         | `1700000000000` (ms)                     | `Number`      |
+//! | 复选框 Checkbox                      | `true`                                   | `Checkbox`    |
+//! | 多选 MultiSelect                     | `["a","b"]`                              | `MultiSelect` |
+//! | 人员 Person, 创建人/修改人           | `[{"id":"ou_..","name":".."}]`           | `Persons`     |
+//! | 超链接 Url                           | `{"text":"..","link":".."}`              | `Url`         |
+//! | 附件 Attachment                      | `[{"file_token":"..","name":".."}]`      | `Attachments` |
+//! | 单向/双向关联 Link                   | `[{"record_ids":[..],"text":".."}]`      | `Links`       |
+//! | 公式 Formula, 查找引用 Lookup        | `{"type":1,"value":[..]}`                | `Computed`    |
+
+use serde::{Deserialize, Deserializer};
+use std::collections::HashMap;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TextSegment {
+    pub text: String,
+    /// Target of an inline link segment
+    pub link: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Person {
+    pub id: String,
+    pub name: Option<String>,
+    pub en_name: Option<String>,
+    pub email: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Attachment {
+    pub file_token: String,
+    pub name: String,
+    pub size: Option<u64>,
+    pub mime_type: Option<String>,
+    pub url: Option<String>,
+    pub tmp_url: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct LinkedRecord {
+    pub record_ids: Vec<String>,
+    /// Display text of the linked record(s)
+    pub text: Option<String>,
+    pub table_id: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum BitableValue {
+    Null,
+    Text(Vec<TextSegment>),
+    Number(f64),
+    Checkbox(bool),
+    MultiSelect(Vec<String>),
+    Persons(Vec<Person>),
+    Url {
+        text: String,
+        link: String,
+    },
+    Attachments(Vec<Attachment>),
+    Links(Vec<LinkedRecord>),
+    /// Formula or lookup result; `value_type` is the type of the computed value
+    Computed {
+        value_type: i32,
+        value: Box<BitableValue>,
+    },
+    /// Array of values with no more specific shape (e.g. a numeric lookup)
+    List(Vec<BitableValue>),
+    /// Anything not recognised above
+    Other(serde_json::Value),
+}
+
+/// A column holds a value of the wrong type for what the parser expects
+#[derive(Debug, Clone, thiserror::Error)]
+#[error("Field '{field}' should be {expected}, got {found}")]
+pub struct ValueError {
+    pub field: String,
+    pub expected: &'static str,
+    pub found: &'static str,
+}
+
+fn str_of(v: &serde_json::Value, key: &str) -> Option<String> {
+    v.get(key).and_then(|s| s.as_str()).map(str::to_string)
+}
+
+impl BitableValue {
+    pub fn from_json(value: &serde_json::Value) -> Self {
+        use serde_json::Value;
+        match value {
+            Value::Null => BitableValue::Null,
+            Value::Bool(b) => BitableValue::Checkbox(*b),
+            Value::Number(n) => n
+                .as_f64()
+                .map(BitableValue::Number)
+                .unwrap_or(BitableValue::Null),
+            Value::String(s) => BitableValue::Text(vec![TextSegment {
+                text: s.clone(),
+                link: None,
+            }]),
+            Value::Array(items) => Self::from_array(items),
+            Value::Object(obj) => {
+                if let Some(ids) = obj.get("link_record_ids").and_then(|v| v.as_array()) {
+                    return BitableValue::Links(vec![LinkedRecord {
+                        record_ids: ids
+                            .iter()
+                            .filter_map(|i| i.as_str())
+                            .map(str::to_string)
+                            .collect(),
+                        text: None,
+                        table_id: None,
+                    }]);
+                }
+                if let (Some(value_type), Some(inner)) =
+                    (obj.get("type").and_then(|t| t.as_i64()), obj.get("value"))
+                {
+                    return BitableValue::Computed {
+                        value_type: value_type as i32,
+                        value: Box::new(Self::from_json(inner)),
+                    };
+                }
+                if let Some(link) = str_of(value, "link") {
+                    return BitableValue::Url {
+                        text: str_of(value, "text").unwrap_or_else(|| link.clone()),
+                        link,
+                    };
+                }
+                if let Some(text) = str_of(value, "text") {
+                    return BitableValue::Text(vec![TextSegment { text, link: None }]);
+                }
+                BitableValue::Other(value.clone())
+            }
+        }
+    }
+
+    fn from_array(items: &[serde_json::Value]) -> Self {
+        let Some(first) = items.first() else {
+            return BitableValue::Null;
+        };
+
+        if items.iter().all(|i| i.is_string()) {
+            return BitableValue::MultiSelect(
+                items
+                    .iter()
+                    .filter_map(|i| i.as_str())
+                    .map(str::to_string)
+                    .collect(),
+            );
+        }
+        if !items.iter().all(|i| i.is_object()) {
+            return BitableValue::List(items.iter().map(Self::from_json).collect());
+        }
+
+        if first.get("file_token").is_some() {
+            return BitableValue::Attachments(
+                items
+                    .iter()
+                    .map(|i| Attachment {
+                        file_token: str_of(i, "file_token").unwrap_or_default(),
+                        name: str_of(i, "name").unwrap_or_default(),
+                        size: i.get("size").and_then(|s| s.as_u64()),
+                        mime_type: str_of(i, "type"),
+                        url: str_of(i, "url"),
+                        tmp_url: str_of(i, "tmp_url"),
+                    })
+                    .collect(),
+            );
+        }
+        if first.get("record_ids").is_some() || first.get("record_id").is_some() {
+            return BitableValue::Links(
+                items
+                    .iter()
+                    .map(|i| LinkedRecord {
+                        record_ids: match i.get("record_ids").and_then(|r| r.as_array()) {
+                            Some(ids) => ids
+                                .iter()
+                                .filter_map(|r| r.as_str())
+                                .map(str::to_string)
+                                .collect(),
+                            None => str_of(i, "record_id").into_iter().collect(),
+                        },
+                        text: str_of(i, "text"),
+                        table_id: str_of(i, "table_id"),
+                    })
+                    .collect(),
+            );
+        }
+        if first.get("text").is_some() {
+            return BitableValue::Text(
+                items
+                    .iter()
+                    .map(|i| TextSegment {
+                        text: str_of(i, "text").unwrap_or_default(),
+                        link: str_of(i, "link"),
+                    })
+                    .collect(),
+            );
+        }
+        if first.get("id").is_some()
+            && (first.get("name").is_some()
+                || first.get("en_name").is_some()
+                || first.get("email").is_some())
+        {
+            return BitableValue::Persons(
+                items
+                    .iter()
+                    .map(|i| Person {
+                        id: str_of(i, "id").unwrap_or_default(),
+                        name: str_of(i, "name"),
+                        en_name: str_of(i, "en_name"),
+                        email: str_of(i, "email"),
+                    })
+                    .collect(),
+            );
+        }
+
+        BitableValue::List(items.iter().map(Self::from_json).collect())
+    }
+
+    /// Short name of the value's shape, for error messages
+    pub fn kind(&self) -> &'static str {
+        match self {
+            BitableValue::Null => "empty",
+            BitableValue::Text(_) => "text",
+            BitableValue::Number(_) => "number",
+            BitableValue::Checkbox(_) => "checkbox",
+            BitableValue::MultiSelect(_) => "multi-select",
+            BitableValue::Persons(_) => "person",
+            BitableValue::Url { .. } => "URL",
+            BitableValue::Attachments(_) => "attachment",
+            BitableValue::Links(_) => "link",
+            BitableValue::Computed { .. } => "formula/lookup",
+            BitableValue::List(_) => "list",
+            BitableValue::Other(_) => "unknown value",
+        }
+    }
+
+    /// Unwrap formula/lookup results and single-element lists
+    fn resolved(&self) -> &BitableValue {
+        match self {
+            BitableValue::Computed { value, .. } => value.resolved(),
+            BitableValue::List(items) if items.len() == 1 => items[0].resolved(),
+            other => other,
+        }
+    }
+
+    pub fn as_text(&self) -> Option<String> {
+        match self.resolved() {
+            BitableValue::Text(segments) => {
+                Some(segments.iter().map(|s| s.text.as_str()).collect())
+            }
+            BitableValue::Number(n) => Some(n.to_string()),
+            BitableValue::Url { text, .. } => Some(text.clone()),
+            BitableValue::List(items) => {
+                let parts: Option<Vec<String>> = items.iter().map(|i| i.as_text()).collect();
+                parts.map(|p| p.join(","))
+            }
+            _ => None,
+        }
+    }
+
+    pub fn as_number(&self) -> Option<f64> {
+        match self.resolved() {
+            BitableValue::Number(n) => Some(*n),
+            // Sometimes numbers come as strings
+            BitableValue::Text(_) => self.as_text()?.trim().parse().ok(),
+            _ => None,
+        }
+    }
+
+    pub fn as_bool(&self) -> Option<bool> {
+        match self.resolved() {
+            BitableValue::Checkbox(b) => Some(*b),
+            _ => None,
+        }
+    }
+
+    /// Date, created-time and modified-time fields (milliseconds since epoch)
+    pub fn as_datetime(&self) -> Option<chrono::DateTime<chrono::Utc>> {
+        match self.resolved() {
+            BitableValue::Number(ms) => chrono::DateTime::from_timestamp_millis(*ms as i64),
+            _ => None,
+        }
+    }
+
+    pub fn as_multi_select(&self) -> Option<Vec<String>> {
+        match self.resolved() {
+            BitableValue::MultiSelect(options) => Some(options.clone()),
+            BitableValue::Text(_) => self.as_text().map(|t| vec![t]),
+            _ => None,
+        }
+    }
+
+    pub fn as_persons(&self) -> Option<&[Person]> {
+        match self.resolved() {
+            BitableValue::Persons(p) => Some(p),
+            _ => None,
+        }
+    }
+
+    pub fn as_url(&self) -> Option<String> {
+        match self.resolved() {
+            BitableValue::Url { link, .. } => Some(link.clone()),
+            BitableValue::Text(segments) => segments
+                .iter()
+                .find_map(|s| s.link.clone())
+                .or_else(|| self.as_text()),
+            _ => None,
+        }
+    }
+
+    pub fn as_attachments(&self) -> Option<&[Attachment]> {
+        match self.resolved() {
+            BitableValue::Attachments(a) => Some(a),
+            _ => None,
+        }
+    }
+
+    pub fn as_links(&self) -> Option<&[LinkedRecord]> {
+        match self.resolved() {
+            BitableValue::Links(l) => Some(l),
+            _ => None,
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for BitableValue {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let value = serde_json::Value::deserialize(deserializer)?;
+        Ok(Self::from_json(&value))
+    }
+}
+
+// ============================================================
+// Record-level typed access
+// ============================================================
+
+/// Decoded fields of one record with typed, error-reporting accessors.
+///
+/// Every accessor returns `Ok(None)` for a missing or empty cell and
+/// `Err(ValueError)` when the cell holds a value of the wrong type.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(transparent)]
+pub struct RecordFields {
+    values: HashMap<String, BitableValue>,
+}
+
+impl RecordFields {
+    pub fn new(fields: &HashMap<String, serde_json::Value>) -> Self {
+        Self {
+            values: fields
+                .iter()
+                .map(|(k, v)| (k.clone(), BitableValue::from_json(v)))
+                .collect(),
+        }
+    }
+
+    #[allow(dead_code)]
+    pub fn get(&self, key: &str) -> Option<&BitableValue> {
+        self.values.get(key).filter(|v| **v != BitableValue::Null)
+    }
+
+    /// Column names present in the record
+    #[allow(dead_code)]
+    pub fn keys(&self) -> impl Iterator<Item = &str> {
+        self.values.keys().map(|k| k.as_str())
+    }
+
+    fn typed<'a, T>(
+        &'a self,
+        key: &str,
+        expected: &'static str,
+        access: impl FnOnce(&'a BitableValue) -> Option<T>,
+    ) -> Result<Option<T>, ValueError> {
+        let Some(value) = self.values.get(key) else {
+            return Ok(None);
+        };
+        if *value == BitableValue::Null {
+            return Ok(None);
+        }
+        match access(value) {
+            Some(v) => Ok(Some(v)),
+            None => Err(ValueError {
+                field: key.to_string(),
+                expected,
+                found: value.kind(),
+            }),
+        }
+    }
+
+    pub fn text(&self, key: &str) -> Result<Option<String>, ValueError> {
+        self.typed(key, "text", |v| v.as_text())
+    }
+
+    pub fn number(&self, key: &str) -> Result<Option<f64>, ValueError> {
+        self.typed(key, "a number", |v| v.as_number())
+    }
+
+    /// Checkbox; an empty cell means unchecked
+    pub fn checkbox(&self, key: &str) -> Result<bool, ValueError> {
+        Ok(self
+            .typed(key, "a checkbox", |v| v.as_bool())?
+            .unwrap_or(false))
+    }
+
+    pub fn single_select(&self, key: &str) -> Result<Option<String>, ValueError> {
+        self.typed(key, "a single-select option", |v| match v.resolved() {
+            BitableValue::Text(_) => v.as_text(),
+            _ => None,
+        })
+    }
+
+    pub fn multi_select(&self, key: &str) -> Result<Option<Vec<String>>, ValueError> {
+        self.typed(key, "multi-select options", |v| v.as_multi_select())
+    }
+
+    pub fn datetime(&self, key: &str) -> Result<Option<chrono::DateTime<chrono::Utc>>, ValueError> {
+        self.typed(key, "a date", |v| v.as_datetime())
+    }
+
+    #[allow(dead_code)]
+    pub fn persons(&self, key: &str) -> Result<Option<&[Person]>, ValueError> {
+        self.typed(key, "a person", |v| v.as_persons())
+    }
+
+    pub fn phone(&self, key: &str) -> Result<Option<String>, ValueError> {
+        self.typed(key, "a phone number", |v| match v.resolved() {
+            BitableValue::Text(_) | BitableValue::Number(_) => v.as_text(),
+            _ => None,
+        })
+    }
+
+    #[allow(dead_code)]
+    pub fn url(&self, key: &str) -> Result<Option<String>, ValueError> {
+        self.typed(key, "a URL", |v| v.as_url())
+    }
+
+    pub fn attachments(&self, key: &str) -> Result<Option<&[Attachment]>, ValueError> {
+        self.typed(key, "an attachment", |v| v.as_attachments())
+    }
+
+    /// First attachment in the cell
+    pub fn attachment(&self, key: &str) -> Result<Option<&Attachment>, ValueError> {
+        Ok(self.attachments(key)?.and_then(|a| a.first()))
+    }
+
+    pub fn links(&self, key: &str) -> Result<Option<&[LinkedRecord]>, ValueError> {
+        self.typed(key, "a link", |v| v.as_links())
+    }
+
+    /// Display text of the first linked record
+    pub fn link_text(&self, key: &str) -> Result<Option<String>, ValueError> {
+        Ok(self
+            .links(key)?
+            .and_then(|l| l.first())
+            .and_then(|l| l.text.clone()))
+    }
+
+    /// record_id of the first linked record
+    pub fn link_record_id(&self, key: &str) -> Result<Option<String>, ValueError> {
+        Ok(self
+            .links(key)?
+            .and_then(|l| l.first())
+            .and_then(|l| l.record_ids.first().cloned()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn record(value: serde_json::Value) -> RecordFields {
+        serde_json::from_value(value).unwrap()
+    }
+
+    #[test]
+    fn decodes_text_segments_and_plain_strings() {
+        let f = record(json!({
+            "名称": [{"type": "text", "text": "古越"}, {"type": "text", "text": "龙山"}],
+            "编码": "GYL-5",
+        }));
+        assert_eq!(f.text("名称").unwrap().as_deref(), Some("古越龙山"));
+        assert_eq!(f.text("编码").unwrap().as_deref(), Some("GYL-5"));
+        assert_eq!(f.text("不存在").unwrap(), None);
+    }
+
+    #[test]
+    fn numbers_accept_numeric_strings() {
+        let f = record(json!({"零售价": 128.5, "库存": "12"}));
+        assert_eq!(f.number("零售价").unwrap(), Some(128.5));
+        assert_eq!(f.number("库存").unwrap(), Some(12.0));
+    }
+
+    #[test]
+    fn wrong_type_is_an_error() {
+        let f = record(json!({
+            "零售价": [{"file_token": "box", "name": "a.png"}],
+            "热销": "yes",
+        }));
+        let err = f.number("零售价").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Field '零售价' should be a number, got attachment"
+        );
+        assert!(f.checkbox("热销").is_err());
+    }
+
+    #[test]
+    fn decodes_attachments_and_links() {
+        let f = record(json!({
+            "商品主图": [{"file_token": "boxA", "name": "p.png", "size": 42, "tmp_url": "https://t"}],
+            "品牌": [{"record_ids": ["rec1"], "table_id": "tblB", "text": "古越龙山", "type": "text"}],
+            "旧关联": {"link_record_ids": ["rec9"]},
+        }));
+        let att = f.attachment("商品主图").unwrap().unwrap();
+        assert_eq!(att.file_token, "boxA");
+        assert_eq!(att.size, Some(42));
+        assert_eq!(f.link_text("品牌").unwrap().as_deref(), Some("古越龙山"));
+        assert_eq!(f.link_record_id("品牌").unwrap().as_deref(), Some("rec1"));
+        assert_eq!(f.link_record_id("旧关联").unwrap().as_deref(), Some("rec9"));
+    }
+
+    #[test]
+    fn decodes_select_url_person_and_datetime() {
+        let f = record(json!({
+            "单位": "瓶",
+            "标签": ["热销", "礼盒"],
+            "官网": {"text": "官网", "link": "https://example.com"},
+            "负责人": [{"id": "ou_1", "name": "店长"}],
+            "上架日期": 1700000000000i64,
+            "空": null,
+        }));
+        assert_eq!(f.single_select("单位").unwrap().as_deref(), Some("瓶"));
+        assert_eq!(
+            f.multi_select("标签").unwrap().unwrap(),
+            vec!["热销", "礼盒"]
+        );
+        assert_eq!(
+            f.url("官网").unwrap().as_deref(),
+            Some("https://example.com")
+        );
+        assert_eq!(
+            f.persons("负责人").unwrap().unwrap()[0].name.as_deref(),
+            Some("店长")
+        );
+        assert_eq!(
+            f.datetime("上架日期").unwrap().unwrap().timestamp_millis(),
+            1700000000000
+        );
+        assert_eq!(f.text("空").unwrap(), None);
+    }
+
+    #[test]
+    fn computed_values_unwrap_to_their_result() {
+        let f = record(json!({
+            "毛利率": {"type": 2, "value": [0.35]},
+            "品牌产地": {"type": 1, "value": [{"type": "text", "text": "浙江绍兴"}]},
+        }));
+        assert_eq!(f.number("毛利率").unwrap(), Some(0.35));
+        assert_eq!(f.text("品牌产地").unwrap().as_deref(), Some("浙江绍兴"));
+    }
+}
@@ -1,3 +1,4 @@
 pub mod bitable_records;
+pub mod bitable_value;
 pub mod mock_data;
 pub mod product;
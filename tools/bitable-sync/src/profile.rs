@@ -0,0 +1,405 @@
+//! `bitable-sync.toml`: named profiles, each with its own Feishu app, base,
+//! tables, repo and output options, picked with `--profile`:
+//!
+//! ```toml
+//! default_profile = "test"
+//!
+//! [profiles.test]
+//! app_id = "cli_xxx"
+//! app_token = "https://xxx.feishu.cn/base/bascnTest"
+//! repo = "../family-business-test"
+//! push = false
+//!
+//! [profiles.test.tables.products]
+//! view = "全部商品"
+//!
+//! [profiles.shop]
+//! app_id = "cli_yyy"
+//! app_token = "bascnShop"
+//! ```
+//!
+//! A profile stands in for the env vars it names (`app_id` is
+//! `FEISHU_APP_ID` and so on). Env vars still override it. Without a config
+//! file, settings come from the environment and `.env.txt`/`.env` as before;
+//! with a profile, the env file is not read, so ids of one base can't leak
+//! into another. Rather than `app_secret`, a profile can name an
+//! `app_secret_file` or an `app_secret_command` (see `secret`).
+
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use crate::config::{column_var, TableKind, ENV_FILE};
+use crate::secret::redact;
+
+/// Config file looked for in the working directory
+pub const CONFIG_FILE: &str = "bitable-sync.toml";
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ConfigFile {
+    default_profile: Option<String>,
+    #[serde(default)]
+    profiles: BTreeMap<String, Profile>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Profile {
+    pub feishu_base_url: Option<String>,
+    pub app_id: Option<String>,
+    pub app_secret: Option<String>,
+    /// File holding the app secret (relative to the config file)
+    pub app_secret_file: Option<PathBuf>,
+    /// Shell command printing the app secret, e.g. `pass show feishu`
+    pub app_secret_command: Option<String>,
+    /// App token, or a Bitable/wiki link
+    pub app_token: Option<String>,
+    /// family-business checkout (relative to the config file)
+    pub repo: Option<PathBuf>,
+    pub state_dir: Option<PathBuf>,
+    pub backup_dir: Option<PathBuf>,
+    /// Commit and push after sync (default true)
+    pub push: Option<bool>,
+    #[serde(default)]
+    pub tables: BTreeMap<TableKind, ProfileTable>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ProfileTable {
+    /// Table id or link
+    pub id: Option<String>,
+    pub name: Option<String>,
+    pub view: Option<String>,
+    /// Column to read each model field from, e.g. `retail_price = "售价"`
+    #[serde(default)]
+    pub columns: BTreeMap<String, String>,
+}
+
+impl Profile {
+    /// The profile's values under the env var names they stand in for
+    fn vars(&self, base_dir: &Path) -> BTreeMap<String, String> {
+        let path = |p: &PathBuf| base_dir.join(p).to_string_lossy().into_owned();
+        let mut vars = BTreeMap::new();
+        let mut set = |key: String, value: Option<String>| {
+            if let Some(value) = value {
+                vars.insert(key, value);
+            }
+        };
+        set("FEISHU_BASE_URL".into(), self.feishu_base_url.clone());
+        set("FEISHU_APP_ID".into(), self.app_id.clone());
+        set("FEISHU_APP_SECRET".into(), self.app_secret.clone());
+        set(
+            "FEISHU_APP_SECRET_FILE".into(),
+            self.app_secret_file.as_ref().map(path),
+        );
+        set(
+            "FEISHU_APP_SECRET_COMMAND".into(),
+            self.app_secret_command.clone(),
+        );
+        set("BITABLE_APP_TOKEN".into(), self.app_token.clone());
+        set("FAMILY_BUSINESS_REPO".into(), self.repo.as_ref().map(path));
+        set("SYNC_STATE_DIR".into(), self.state_dir.as_ref().map(path));
+        set("BACKUP_DIR".into(), self.backup_dir.as_ref().map(path));
+        set("SYNC_PUSH".into(), self.push.map(|p| p.to_string()));
+        for (kind, table) in &self.tables {
+            let suffix = kind.env_suffix();
+            set(format!("TABLE_ID_{}", suffix), table.id.clone());
+            set(format!("TABLE_NAME_{}", suffix), table.name.clone());
+            set(format!("VIEW_{}", suffix), table.view.clone());
+            for (model, column) in &table.columns {
+                set(column_var(*kind, model), Some(column.clone()));
+            }
+        }
+        vars
+    }
+}
+
+/// Where a setting came from
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Source {
+    Env,
+    Profile { name: String, file: PathBuf },
+    EnvFile(PathBuf),
+}
+
+impl fmt::Display for Source {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Source::Env => write!(f, "environment"),
+            Source::Profile { name, file } => {
+                write!(f, "profile '{}' ({})", name, file.display())
+            }
+            Source::EnvFile(path) => write!(f, "{}", path.display()),
+        }
+    }
+}
+
+/// Settings by env var name, looked up in the process environment, then
+/// the selected profile, then the env file
+#[derive(Clone, Default)]
+pub struct Settings {
+    layers: Vec<(Source, BTreeMap<String, String>)>,
+    profile: Option<String>,
+}
+
+impl Settings {
+    /// `file` defaults to `bitable-sync.toml` when it exists; `profile` to
+    /// the file's `default_profile` (or its only profile)
+    pub fn load(profile: Option<&str>, file: Option<&Path>) -> Result<Self> {
+        let mut settings = Settings {
+            layers: vec![(Source::Env, std::env::vars().collect())],
+            profile: None,
+        };
+
+        let file = match file {
+            Some(file) => Some(file.to_path_buf()),
+            None => Some(PathBuf::from(CONFIG_FILE)).filter(|f| f.exists()),
+        };
+        match file {
+            Some(file) => settings.add_profile(&file, profile)?,
+            None => {
+                anyhow::ensure!(
+                    profile.is_none(),
+                    "--profile needs a config file, but {} does not exist",
+                    CONFIG_FILE
+                );
+                settings.add_env_file();
+            }
+        }
+        Ok(settings)
+    }
+
+    fn add_profile(&mut self, file: &Path, profile: Option<&str>) -> Result<()> {
+        let content = std::fs::read_to_string(file)
+            .with_context(|| format!("Failed to read {}", file.display()))?;
+        let parsed: ConfigFile = toml::from_str(&content)
+            .with_context(|| format!("Failed to parse {}", file.display()))?;
+        let names = || {
+            parsed
+                .profiles
+                .keys()
+                .cloned()
+                .collect::<Vec<_>>()
+                .join(", ")
+        };
+
+        let name = match (profile, parsed.default_profile.as_deref()) {
+            (Some(name), _) | (None, Some(name)) => name.to_string(),
+            (None, None) if parsed.profiles.len() == 1 => {
+                parsed.profiles.keys().next().cloned().unwrap_or_default()
+            }
+            (None, None) => anyhow::bail!(
+                "{} has several profiles; pick one with --profile (available: {})",
+                file.display(),
+                names()
+            ),
+        };
+        let selected = parsed.profiles.get(&name).with_context(|| {
+            format!(
+                "No profile '{}' in {} (available: {})",
+                name,
+                file.display(),
+                names()
+            )
+        })?;
+
+        for (kind, table) in &selected.tables {
+            let spec = crate::schema::table_spec(*kind);
+            for model in table.columns.keys() {
+                anyhow::ensure!(
+                    spec.fields.iter().any(|f| f.model.as_ref() == Some(model)),
+                    "Profile '{}': {:?} has no model field '{}' to map a column to",
+                    name,
+                    kind,
+                    model
+                );
+            }
+        }
+
+        let base_dir = file.parent().unwrap_or(Path::new("."));
+        tracing::info!("Using profile '{}' from {}", name, file.display());
+        self.layers.push((
+            Source::Profile {
+                name: name.clone(),
+                file: file.to_path_buf(),
+            },
+            selected.vars(base_dir),
+        ));
+        self.profile = Some(name);
+        Ok(())
+    }
+
+    /// `.env.txt`, else `.env`, without touching the process environment
+    fn add_env_file(&mut self) {
+        for path in [ENV_FILE, ".env"] {
+            let Ok(iter) = dotenvy::from_filename_iter(path) else {
+                continue;
+            };
+            let vars = iter
+                .filter_map(|item| match item {
+                    Ok(pair) => Some(pair),
+                    Err(e) => {
+                        tracing::warn!("Ignoring invalid line in {}: {}", path, e);
+                        None
+                    }
+                })
+                .collect();
+            self.layers
+                .push((Source::EnvFile(PathBuf::from(path)), vars));
+            return;
+        }
+    }
+
+    /// Non-empty value of `key` from the first layer that sets it
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.lookup(key).map(|(value, _)| value)
+    }
+
+    /// Like `get`, with where the value came from
+    pub fn lookup(&self, key: &str) -> Option<(&str, &Source)> {
+        self.layers.iter().find_map(|(source, vars)| {
+            vars.get(key)
+                .filter(|v| !v.is_empty())
+                .map(|v| (v.as_str(), source))
+        })
+    }
+
+    /// The first layer setting any of `keys`, with the key it sets (the
+    /// earliest in `keys` if it sets several) and its value
+    pub fn first_of<'a>(&self, keys: &[&'a str]) -> Option<(&'a str, &str)> {
+        self.layers.iter().find_map(|(_, vars)| {
+            keys.iter().find_map(|&key| {
+                vars.get(key)
+                    .filter(|v| !v.is_empty())
+                    .map(|v| (key, v.as_str()))
+            })
+        })
+    }
+
+    #[cfg(test)]
+    pub fn from_layers(layers: Vec<(Source, BTreeMap<String, String>)>) -> Self {
+        Settings {
+            layers,
+            profile: None,
+        }
+    }
+
+    /// Where settings were read from, highest precedence first
+    pub fn sources(&self) -> impl Iterator<Item = &Source> {
+        self.layers.iter().map(|(source, _)| source)
+    }
+
+    /// Name of the selected profile
+    pub fn profile(&self) -> Option<&str> {
+        self.profile.as_deref()
+    }
+}
+
+/// Secrets are redacted, so a `Config` can be logged with `{:?}`
+impl fmt::Debug for Settings {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let layers: Vec<_> = self
+            .layers
+            .iter()
+            .map(|(source, vars)| {
+                let vars: BTreeMap<_, _> = vars
+                    .iter()
+                    .map(|(k, v)| (k.as_str(), redact(k, v)))
+                    .collect();
+                (source, vars)
+            })
+            .collect();
+        f.debug_struct("Settings")
+            .field("layers", &layers)
+            .field("profile", &self.profile)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TempDir;
+
+    fn write_config(dir: &TempDir, content: &str) -> PathBuf {
+        let file = dir.path().join(CONFIG_FILE);
+        std::fs::write(&file, content).unwrap();
+        file
+    }
+
+    const TWO_PROFILES: &str = r#"
+default_profile = "test"
+
+[profiles.test]
+app_id = "cli_test"
+app_token = "bascnTest"
+repo = "site"
+push = false
+
+[profiles.test.tables.products]
+id = "tblTest"
+view = "全部商品"
+columns = { retail_price = "售价" }
+
+[profiles.shop]
+app_id = "cli_shop"
+app_token = "bascnShop"
+"#;
+
+    #[test]
+    fn selects_profiles_and_maps_them_to_env_names() {
+        let dir = TempDir::new("profile");
+        let file = write_config(&dir, TWO_PROFILES);
+
+        let test = Settings::load(None, Some(&file)).unwrap();
+        assert_eq!(test.profile(), Some("test"));
+        assert_eq!(test.get("BITABLE_APP_TOKEN"), Some("bascnTest"));
+        assert_eq!(test.get("TABLE_ID_PRODUCTS"), Some("tblTest"));
+        assert_eq!(test.get("VIEW_PRODUCTS"), Some("全部商品"));
+        assert_eq!(test.get("COLUMN_PRODUCTS_RETAIL_PRICE"), Some("售价"));
+        assert_eq!(test.get("SYNC_PUSH"), Some("false"));
+        let repo = file.parent().unwrap().join("site");
+        assert_eq!(test.get("FAMILY_BUSINESS_REPO"), repo.to_str());
+
+        let shop = Settings::load(Some("shop"), Some(&file)).unwrap();
+        assert_eq!(shop.get("BITABLE_APP_TOKEN"), Some("bascnShop"));
+        assert_eq!(shop.get("TABLE_ID_PRODUCTS"), None);
+
+        let err = Settings::load(Some("prod"), Some(&file)).unwrap_err();
+        assert!(err.to_string().contains("available: shop, test"));
+    }
+
+    #[test]
+    fn env_overrides_the_profile() {
+        let mut settings = Settings::default();
+        settings.layers.push((
+            Source::Env,
+            BTreeMap::from([("FEISHU_APP_ID".to_string(), "cli_env".to_string())]),
+        ));
+        let profile = Profile {
+            app_id: Some("cli_file".to_string()),
+            app_token: Some("bascnFile".to_string()),
+            ..Default::default()
+        };
+        let source = Source::Profile {
+            name: "test".to_string(),
+            file: PathBuf::from(CONFIG_FILE),
+        };
+        settings
+            .layers
+            .push((source.clone(), profile.vars(Path::new("."))));
+
+        assert_eq!(
+            settings.lookup("FEISHU_APP_ID"),
+            Some(("cli_env", &Source::Env))
+        );
+        assert_eq!(
+            settings.lookup("BITABLE_APP_TOKEN"),
+            Some(("bascnFile", &source))
+        );
+    }
+}
@@ -0,0 +1,13 @@
+//! Terminal prompts shared by the commands that change or replace a base.
+
+use anyhow::Result;
+use std::io::Write;
+
+/// Ask a yes/no question on the terminal; anything but "y"/"yes" is a no
+pub fn confirm(prompt: &str) -> Result<bool> {
+    print!("{} [y/N] ", prompt);
+    std::io::stdout().flush()?;
+    let mut answer = String::new();
+    std::io::stdin().read_line(&mut answer)?;
+    Ok(matches!(answer.trim(), "y" | "Y" | "yes"))
+}
@@ -0,0 +1,638 @@
+//! Table schemas the parsers expect, shared by `setup` (to create tables),
+//! `check` and `migrate` (to verify an existing base) and the record parsers
+//! (to find their columns). Everything comes from `schema.toml`.
+
+use serde::Deserialize;
+use std::collections::{BTreeMap, HashSet};
+use std::sync::OnceLock;
+
+use crate::columns::ColumnMap;
+use crate::config::TableKind;
+use crate::feishu::bitable::{FieldDef, FieldInfo, FieldType};
+
+const SCHEMA_TOML: &str = include_str!("../schema.toml");
+
+// ============================================================
+// schema.toml
+// ============================================================
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SchemaFile {
+    #[serde(rename = "table")]
+    pub tables: Vec<TableSpec>,
+    /// Extra rows for the guide table
+    #[serde(default, rename = "guide_note")]
+    pub guide_notes: Vec<GuideEntry>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TableSpec {
+    /// Synced table this describes (absent for the guide table)
+    pub kind: Option<TableKind>,
+    pub name: String,
+    pub view: String,
+    /// This is the 使用说明 table, filled from the other tables' `guide`
+    #[serde(default)]
+    pub guide_table: bool,
+    pub guide: Option<GuideEntry>,
+    pub fields: Vec<FieldSpec>,
+    /// Records to seed a newly created table with (field name -> value)
+    #[serde(default)]
+    pub samples: Vec<serde_json::Map<String, serde_json::Value>>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct GuideEntry {
+    /// Row title; defaults to the table name
+    pub table: Option<String>,
+    pub purpose: String,
+    pub required: String,
+    pub notes: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FieldKind {
+    Text,
+    Number,
+    SingleSelect,
+    MultiSelect,
+    Datetime,
+    Checkbox,
+    Person,
+    Phone,
+    Url,
+    Attachment,
+    Link,
+    Lookup,
+    Formula,
+    CreatedTime,
+    ModifiedTime,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct LookupSpec {
+    pub table: TableKind,
+    pub field: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct FieldSpec {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub kind: FieldKind,
+    /// Model field the parsers read this column into
+    pub model: Option<String>,
+    /// Other names the parsers accept for the column (see `columns`)
+    #[serde(default)]
+    pub aliases: Vec<String>,
+    pub format: Option<String>,
+    #[serde(default)]
+    pub options: Vec<String>,
+    pub link: Option<TableKind>,
+    pub lookup: Option<LookupSpec>,
+    pub formula: Option<String>,
+    #[serde(default)]
+    pub optional: bool,
+}
+
+impl FieldSpec {
+    /// Definition to create the field with; `None` for links and lookups,
+    /// which need the ids of other tables
+    fn to_def(&self) -> Option<FieldDef> {
+        let name = self.name.as_str();
+        let format = |default: &'static str| self.format.clone().unwrap_or(default.to_string());
+        let options: Vec<&str> = self.options.iter().map(String::as_str).collect();
+        Some(match self.kind {
+            FieldKind::Text => FieldDef::text(name),
+            FieldKind::Number => FieldDef::number(name, &format("0")),
+            FieldKind::SingleSelect => FieldDef::single_select(name, &options),
+            FieldKind::MultiSelect => FieldDef::multi_select(name, &options),
+            FieldKind::Datetime => FieldDef::datetime(name, &format("yyyy/MM/dd")),
+            FieldKind::Checkbox => FieldDef::checkbox(name),
+            FieldKind::Person => FieldDef::person(name, true),
+            FieldKind::Phone => FieldDef::phone(name),
+            FieldKind::Url => FieldDef::url(name),
+            FieldKind::Attachment => FieldDef::attachment(name),
+            FieldKind::Formula => FieldDef::formula(
+                name,
+                self.formula.as_deref().unwrap_or_default(),
+                &format("0.00"),
+            ),
+            FieldKind::CreatedTime => FieldDef::created_time(name),
+            FieldKind::ModifiedTime => FieldDef::modified_time(name),
+            FieldKind::Link | FieldKind::Lookup => return None,
+        })
+    }
+}
+
+impl TableSpec {
+    /// Column holding `model` (e.g. "retail_price" -> "零售价").
+    ///
+    /// Panics if the schema doesn't map it: the parsers and the embedded
+    /// schema ship together, and a test parses a full record of every table.
+    pub fn column(&self, model: &str) -> &str {
+        self.fields
+            .iter()
+            .find(|f| f.model.as_deref() == Some(model))
+            .map(|f| f.name.as_str())
+            .unwrap_or_else(|| {
+                panic!(
+                    "schema.toml: table '{}' has no field for '{}'",
+                    self.name, model
+                )
+            })
+    }
+}
+
+/// The embedded `schema.toml`
+pub fn schema_file() -> &'static SchemaFile {
+    static SCHEMA: OnceLock<SchemaFile> = OnceLock::new();
+    SCHEMA.get_or_init(|| match toml::from_str(SCHEMA_TOML) {
+        Ok(schema) => schema,
+        Err(e) => panic!("schema.toml is invalid: {}", e),
+    })
+}
+
+/// Spec of a synced table
+pub fn table_spec(kind: TableKind) -> &'static TableSpec {
+    schema_file()
+        .tables
+        .iter()
+        .find(|t| t.kind == Some(kind))
+        .unwrap_or_else(|| panic!("schema.toml has no table for {:?}", kind))
+}
+
+// ============================================================
+// Table schemas for setup / check / migrate
+// ============================================================
+
+/// Table schema definition
+pub struct TableSchema {
+    /// Synced table this describes (`None` for the guide table)
+    pub kind: Option<TableKind>,
+    pub name: &'static str,
+    pub view_name: &'static str,
+    /// Fields to create with the table (no link fields here)
+    pub fields: Vec<FieldDef>,
+    /// Link fields to add after all tables are created (name, linked_table_index)
+    pub links: Vec<(&'static str, usize)>,
+    /// Lookup fields added after links: (field name, linked table index, field to pull)
+    pub lookups: Vec<(&'static str, usize, &'static str)>,
+    /// Fields the parsers can do without (missing is only a warning)
+    pub optional: Vec<&'static str>,
+}
+
+/// All tables in `schema.toml`, in creation order
+pub fn define_schemas() -> Vec<TableSchema> {
+    let tables = &schema_file().tables;
+    let index_of = |kind: TableKind| {
+        tables
+            .iter()
+            .position(|t| t.kind == Some(kind))
+            .unwrap_or_else(|| panic!("schema.toml has no table for {:?}", kind))
+    };
+
+    tables
+        .iter()
+        .map(|t| TableSchema {
+            kind: t.kind,
+            name: &t.name,
+            view_name: &t.view,
+            fields: t.fields.iter().filter_map(FieldSpec::to_def).collect(),
+            links: t
+                .fields
+                .iter()
+                .filter_map(|f| f.link.map(|target| (f.name.as_str(), index_of(target))))
+                .collect(),
+            lookups: t
+                .fields
+                .iter()
+                .filter_map(|f| {
+                    let l = f.lookup.as_ref()?;
+                    Some((f.name.as_str(), index_of(l.table), l.field.as_str()))
+                })
+                .collect(),
+            optional: t
+                .fields
+                .iter()
+                .filter(|f| f.optional)
+                .map(|f| f.name.as_str())
+                .collect(),
+        })
+        .collect()
+}
+
+/// Rows for the guide table: one per table with a `guide`, then the notes
+pub fn guide_records() -> Vec<serde_json::Value> {
+    let schema = schema_file();
+    let Some(guide) = schema.tables.iter().find(|t| t.guide_table) else {
+        return Vec::new();
+    };
+
+    let entries = schema
+        .tables
+        .iter()
+        .filter_map(|t| t.guide.as_ref().map(|g| (t.name.as_str(), g)))
+        .chain(schema.guide_notes.iter().map(|g| ("", g)));
+    entries
+        .map(|(table_name, g)| {
+            serde_json::json!({"fields": {
+                guide.column("table"): g.table.as_deref().unwrap_or(table_name),
+                guide.column("purpose"): g.purpose,
+                guide.column("required"): g.required,
+                guide.column("notes"): g.notes,
+            }})
+        })
+        .collect()
+}
+
+// ============================================================
+// Schema verification
+// ============================================================
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    /// Harmless, e.g. a column sync doesn't read
+    Info,
+    /// Sync still works, possibly with defaults
+    Warning,
+    /// Sync would misread or drop data
+    Error,
+}
+
+/// One difference between a table and its schema
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Finding {
+    pub severity: Severity,
+    pub field: String,
+    pub message: String,
+}
+
+impl Finding {
+    fn new(severity: Severity, field: &str, message: String) -> Self {
+        Self {
+            severity,
+            field: field.to_string(),
+            message,
+        }
+    }
+}
+
+/// A field as the parsers expect it
+struct ExpectedField<'a> {
+    name: &'a str,
+    field_type: i32,
+    options: Vec<String>,
+    link_target: Option<TableKind>,
+}
+
+impl TableSchema {
+    fn expected_fields<'a>(&'a self, schemas: &[TableSchema]) -> Vec<ExpectedField<'a>> {
+        let plain = self.fields.iter().map(|f| ExpectedField {
+            name: &f.field_name,
+            field_type: f.field_type,
+            options: select_options(f.property.as_ref()),
+            link_target: None,
+        });
+        let links = self.links.iter().map(|(name, index)| ExpectedField {
+            name,
+            field_type: FieldType::SingleLink as i32,
+            options: vec![],
+            link_target: schemas[*index].kind,
+        });
+        let lookups = self.lookups.iter().map(|(name, _, _)| ExpectedField {
+            name,
+            field_type: FieldType::Lookup as i32,
+            options: vec![],
+            link_target: None,
+        });
+        plain.chain(links).chain(lookups).collect()
+    }
+}
+
+/// Compare a table's actual fields with its schema.
+///
+/// `schemas` is the full list from `define_schemas` (link targets are
+/// indexes into it); `cols` says which column each field is read from (an
+/// alias or override counts as present); `table_ids` holds the configured
+/// id of each table.
+pub fn diff_table(
+    schema: &TableSchema,
+    schemas: &[TableSchema],
+    actual: &[FieldInfo],
+    cols: &ColumnMap,
+    table_ids: &BTreeMap<TableKind, String>,
+) -> Vec<Finding> {
+    let mut findings = Vec::new();
+    let expected = schema.expected_fields(schemas);
+
+    for want in &expected {
+        let column = cols.column_for(want.name);
+        let Some(have) = actual.iter().find(|f| f.field_name == column) else {
+            let severity = if schema.optional.contains(&want.name) {
+                Severity::Warning
+            } else {
+                Severity::Error
+            };
+            findings.push(Finding::new(
+                severity,
+                want.name,
+                format!("missing ({})", FieldType::describe(want.field_type)),
+            ));
+            continue;
+        };
+
+        if let Some(severity) = type_mismatch(want.field_type, have.field_type) {
+            findings.push(Finding::new(
+                severity,
+                column,
+                format!(
+                    "is {}, expected {}",
+                    FieldType::describe(have.field_type),
+                    FieldType::describe(want.field_type)
+                ),
+            ));
+            continue;
+        }
+
+        let have_options: HashSet<String> =
+            select_options(have.property.as_ref()).into_iter().collect();
+        let missing: Vec<&str> = want
+            .options
+            .iter()
+            .filter(|o| !have_options.contains(*o))
+            .map(|o| o.as_str())
+            .collect();
+        if !missing.is_empty() && have.field_type == want.field_type {
+            findings.push(Finding::new(
+                Severity::Warning,
+                column,
+                format!("lacks options: {}", missing.join(", ")),
+            ));
+        }
+
+        if let Some(target) = want.link_target {
+            let linked = have
+                .property
+                .as_ref()
+                .and_then(|p| p.get("table_id"))
+                .and_then(|v| v.as_str());
+            if let Some(expected_id) = table_ids.get(&target).filter(|id| !id.is_empty()) {
+                if linked != Some(expected_id.as_str()) {
+                    findings.push(Finding::new(
+                        Severity::Error,
+                        column,
+                        format!(
+                            "links to {}, expected {} ({})",
+                            linked.unwrap_or("nothing"),
+                            target.default_name(),
+                            expected_id
+                        ),
+                    ));
+                }
+            }
+        }
+    }
+
+    for have in actual {
+        if !expected
+            .iter()
+            .any(|e| cols.column_for(e.name) == have.field_name)
+        {
+            findings.push(Finding::new(
+                Severity::Info,
+                &have.field_name,
+                format!(
+                    "extra {} field, not read by sync",
+                    FieldType::describe(have.field_type)
+                ),
+            ));
+        }
+    }
+
+    findings
+}
+
+/// `None` when `actual` is fine for a field expected as `expected`
+fn type_mismatch(expected: i32, actual: i32) -> Option<Severity> {
+    use FieldType::*;
+    let is = |code: i32, types: &[FieldType]| types.iter().any(|t| *t as i32 == code);
+
+    if expected == actual
+        || (is(expected, &[SingleLink, DuplexLink]) && is(actual, &[SingleLink, DuplexLink]))
+    {
+        None
+    } else if is(expected, &[Formula, Lookup]) || is(actual, &[Formula, Lookup]) {
+        // Computed values decode by shape; their result type isn't in the schema
+        Some(Severity::Warning)
+    } else if is(expected, &[Text, SingleSelect, MultiSelect])
+        && is(actual, &[Text, SingleSelect, MultiSelect])
+    {
+        // Text accessors read selects too, and 展示分类 accepts both
+        Some(Severity::Warning)
+    } else {
+        Some(Severity::Error)
+    }
+}
+
+pub fn select_options(property: Option<&serde_json::Value>) -> Vec<String> {
+    property
+        .and_then(|p| p.get("options"))
+        .and_then(|o| o.as_array())
+        .map(|options| {
+            options
+                .iter()
+                .filter_map(|o| o.get("name").and_then(|n| n.as_str()))
+                .map(str::to_string)
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn field(name: &str, field_type: FieldType, property: Option<serde_json::Value>) -> FieldInfo {
+        FieldInfo {
+            field_id: format!("fld_{}", name),
+            field_name: name.to_string(),
+            field_type: field_type as i32,
+            ui_type: None,
+            is_primary: None,
+            property,
+        }
+    }
+
+    /// Products columns resolved against `actual`, with `overrides`
+    fn cols(actual: &[FieldInfo], overrides: &[(&str, &str)]) -> ColumnMap {
+        let available: Vec<String> = actual.iter().map(|f| f.field_name.clone()).collect();
+        let overrides = overrides
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+        ColumnMap::resolve(TableKind::Products, &overrides, &available)
+    }
+
+    /// The products table exactly as `setup` creates it
+    fn products_as_created(
+        schemas: &[TableSchema],
+        brands: &str,
+        categories: &str,
+    ) -> Vec<FieldInfo> {
+        let schema = schemas
+            .iter()
+            .find(|s| s.kind == Some(TableKind::Products))
+            .unwrap();
+        let mut fields: Vec<FieldInfo> = schema
+            .fields
+            .iter()
+            .map(|f| FieldInfo {
+                field_id: String::new(),
+                field_name: f.field_name.clone(),
+                field_type: f.field_type,
+                ui_type: None,
+                is_primary: None,
+                property: f.property.clone(),
+            })
+            .collect();
+        fields.push(field(
+            "品牌",
+            FieldType::SingleLink,
+            Some(serde_json::json!({"table_id": brands})),
+        ));
+        fields.push(field(
+            "分类",
+            FieldType::SingleLink,
+            Some(serde_json::json!({"table_id": categories})),
+        ));
+        fields.push(field("产地", FieldType::Lookup, None));
+        fields
+    }
+
+    #[test]
+    fn schema_file_defines_every_table() {
+        let schemas = define_schemas();
+        assert_eq!(schemas.len(), 7);
+        for kind in TableKind::ALL {
+            assert_eq!(kind.default_name(), table_spec(kind).name);
+        }
+
+        let products = schemas
+            .iter()
+            .find(|s| s.kind == Some(TableKind::Products))
+            .unwrap();
+        // Links and lookups are not created with the table
+        assert!(products.fields.iter().all(|f| f.field_name != "品牌"));
+        assert_eq!(products.links, vec![("品牌", 0), ("分类", 1)]);
+        assert_eq!(products.lookups, vec![("产地", 0, "产地")]);
+        assert!(products.optional.contains(&"毛利率"));
+
+        // A column name (or alias) can only ever mean one field
+        for spec in &schema_file().tables {
+            let mut names = HashSet::new();
+            for f in &spec.fields {
+                for name in std::iter::once(&f.name).chain(&f.aliases) {
+                    assert!(names.insert(name), "'{}' is ambiguous in {}", name, spec.name);
+                }
+            }
+        }
+
+        // One row per synced table plus the notes
+        let guide = guide_records();
+        assert_eq!(guide.len(), 7);
+        assert_eq!(guide[0]["fields"]["表名"], "品牌表 Brands");
+    }
+
+    #[test]
+    fn matching_table_has_no_findings() {
+        let schemas = define_schemas();
+        let ids = BTreeMap::from([
+            (TableKind::Brands, "tblB".to_string()),
+            (TableKind::DisplayCategories, "tblC".to_string()),
+        ]);
+        let products = schemas
+            .iter()
+            .find(|s| s.kind == Some(TableKind::Products))
+            .unwrap();
+        let actual = products_as_created(&schemas, "tblB", "tblC");
+
+        assert_eq!(
+            diff_table(products, &schemas, &actual, &cols(&actual, &[]), &ids),
+            vec![]
+        );
+    }
+
+    #[test]
+    fn aliased_and_overridden_columns_count_as_present() {
+        let schemas = define_schemas();
+        let ids = BTreeMap::from([
+            (TableKind::Brands, "tblB".to_string()),
+            (TableKind::DisplayCategories, "tblC".to_string()),
+        ]);
+        let products = schemas
+            .iter()
+            .find(|s| s.kind == Some(TableKind::Products))
+            .unwrap();
+        let mut actual = products_as_created(&schemas, "tblB", "tblC");
+        for f in &mut actual {
+            match f.field_name.as_str() {
+                "商品ID" => f.field_name = "Product ID".to_string(),
+                "零售价" => f.field_name = "售价".to_string(),
+                "品牌" => f.field_name = "Brand".to_string(),
+                _ => {}
+            }
+        }
+
+        let cols = cols(&actual, &[("retail_price", "售价")]);
+        assert_eq!(diff_table(products, &schemas, &actual, &cols, &ids), vec![]);
+    }
+
+    #[test]
+    fn reports_renamed_mistyped_and_mislinked_fields() {
+        let schemas = define_schemas();
+        let ids = BTreeMap::from([
+            (TableKind::Brands, "tblB".to_string()),
+            (TableKind::DisplayCategories, "tblC".to_string()),
+        ]);
+        let products = schemas
+            .iter()
+            .find(|s| s.kind == Some(TableKind::Products))
+            .unwrap();
+        let mut actual = products_as_created(&schemas, "tblOld", "tblC");
+        for f in &mut actual {
+            match f.field_name.as_str() {
+                "零售价" => f.field_name = "价格".to_string(),
+                "库存" => f.field_type = FieldType::Text as i32,
+                "单位" => f.property = Some(serde_json::json!({"options": [{"name": "瓶"}]})),
+                _ => {}
+            }
+        }
+        actual.retain(|f| f.field_name != "上架日期");
+
+        let findings = diff_table(products, &schemas, &actual, &cols(&actual, &[]), &ids);
+        let summary: Vec<(Severity, &str)> = findings
+            .iter()
+            .map(|f| (f.severity, f.field.as_str()))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                (Severity::Warning, "单位"),
+                (Severity::Error, "零售价"),
+                (Severity::Error, "库存"),
+                (Severity::Warning, "上架日期"),
+                (Severity::Error, "品牌"),
+                (Severity::Info, "价格"),
+            ]
+        );
+        assert_eq!(findings[0].message, "lacks options: 箱, 坛, 盒");
+        assert_eq!(findings[2].message, "is Text, expected Number");
+    }
+}
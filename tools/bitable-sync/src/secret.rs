@@ -0,0 +1,186 @@
+//! The Feishu app secret, kept out of logs and debug output.
+//!
+//! It can be given directly (`FEISHU_APP_SECRET`), read from a file
+//! (`FEISHU_APP_SECRET_FILE`), or printed by a command such as
+//! `pass show feishu/app-secret` (`FEISHU_APP_SECRET_COMMAND`), so it never
+//! has to sit in a plaintext dotfile.
+
+use anyhow::{Context, Result};
+use std::fmt;
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+use crate::profile::Settings;
+
+/// Settings whose values must never be printed
+pub const SECRET_KEYS: &[&str] = &["FEISHU_APP_SECRET"];
+
+const MASK: &str = "[redacted]";
+
+/// A string that prints as `[redacted]`; use `expose` where the real value
+/// is needed
+#[derive(Clone, Default, PartialEq, Eq)]
+pub struct Secret(String);
+
+impl Secret {
+    pub fn expose(&self) -> &str {
+        &self.0
+    }
+}
+
+impl From<String> for Secret {
+    fn from(value: String) -> Self {
+        Self(value)
+    }
+}
+
+impl From<&str> for Secret {
+    fn from(value: &str) -> Self {
+        Self(value.to_string())
+    }
+}
+
+impl fmt::Debug for Secret {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(MASK)
+    }
+}
+
+impl fmt::Display for Secret {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(MASK)
+    }
+}
+
+/// `value`, or `[redacted]` when `key` names a secret
+pub fn redact<'a>(key: &str, value: &'a str) -> &'a str {
+    if SECRET_KEYS.contains(&key) {
+        MASK
+    } else {
+        value
+    }
+}
+
+/// The app secret from whichever of `FEISHU_APP_SECRET`,
+/// `FEISHU_APP_SECRET_FILE` and `FEISHU_APP_SECRET_COMMAND` the highest
+/// settings layer sets (so an env var still overrides a profile's command)
+pub fn app_secret(settings: &Settings) -> Result<Secret> {
+    let keys = [
+        "FEISHU_APP_SECRET",
+        "FEISHU_APP_SECRET_FILE",
+        "FEISHU_APP_SECRET_COMMAND",
+    ];
+    let (key, value) = settings
+        .first_of(&keys)
+        .with_context(|| format!("Feishu app secret not set (set one of {})", keys.join(", ")))?;
+    let secret = match key {
+        "FEISHU_APP_SECRET_FILE" => read_secret_file(Path::new(value))?,
+        "FEISHU_APP_SECRET_COMMAND" => run_secret_command(value)?,
+        _ => value.to_string(),
+    };
+    anyhow::ensure!(!secret.is_empty(), "{} gave an empty app secret", key);
+    Ok(Secret(secret))
+}
+
+fn read_secret_file(path: &Path) -> Result<String> {
+    let content = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read secret file {}", path.display()))?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        if let Ok(meta) = std::fs::metadata(path) {
+            if meta.permissions().mode() & 0o077 != 0 {
+                tracing::warn!(
+                    "Secret file {} is readable by other users; chmod 600 it",
+                    path.display()
+                );
+            }
+        }
+    }
+    Ok(content.trim().to_string())
+}
+
+/// Run `command` through the shell and take its trimmed stdout. Stdin and
+/// stderr stay attached, so password managers can prompt.
+fn run_secret_command(command: &str) -> Result<String> {
+    let mut shell = if cfg!(windows) {
+        let mut c = Command::new("cmd");
+        c.arg("/C");
+        c
+    } else {
+        let mut c = Command::new("sh");
+        c.arg("-c");
+        c
+    };
+    let output = shell
+        .arg(command)
+        .stdin(Stdio::inherit())
+        .stderr(Stdio::inherit())
+        .output()
+        .with_context(|| format!("Failed to run secret command `{}`", command))?;
+    anyhow::ensure!(
+        output.status.success(),
+        "Secret command `{}` failed ({})",
+        command,
+        output.status
+    );
+    let stdout = String::from_utf8(output.stdout)
+        .with_context(|| format!("Secret command `{}` printed invalid UTF-8", command))?;
+    Ok(stdout.trim().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::profile::Source;
+    use std::collections::BTreeMap;
+
+    fn settings(layers: Vec<Vec<(&str, &str)>>) -> Settings {
+        let layers = layers
+            .into_iter()
+            .map(|vars| {
+                let vars = vars
+                    .into_iter()
+                    .map(|(k, v)| (k.to_string(), v.to_string()))
+                    .collect::<BTreeMap<_, _>>();
+                (Source::Env, vars)
+            })
+            .collect();
+        Settings::from_layers(layers)
+    }
+
+    #[test]
+    fn secret_never_prints() {
+        let secret = Secret::from("hunter2");
+        assert_eq!(format!("{:?} {}", secret, secret), "[redacted] [redacted]");
+        assert_eq!(secret.expose(), "hunter2");
+        assert_eq!(redact("FEISHU_APP_SECRET", "hunter2"), "[redacted]");
+        assert_eq!(redact("FEISHU_APP_ID", "cli_x"), "cli_x");
+    }
+
+    #[test]
+    fn secret_comes_from_the_highest_layer() {
+        let dir = crate::testing::TempDir::new("secret");
+        let file = dir.path().join("app-secret");
+        std::fs::write(&file, "from-file\n").unwrap();
+        let path = file.to_str().unwrap();
+
+        let s = settings(vec![
+            vec![("FEISHU_APP_SECRET_FILE", path)],
+            vec![("FEISHU_APP_SECRET", "from-profile")],
+        ]);
+        assert_eq!(app_secret(&s).unwrap().expose(), "from-file");
+
+        let s = settings(vec![
+            vec![],
+            vec![("FEISHU_APP_SECRET_COMMAND", "echo from-command")],
+        ]);
+        assert_eq!(app_secret(&s).unwrap().expose(), "from-command");
+
+        let s = settings(vec![vec![("FEISHU_APP_SECRET_COMMAND", "exit 3")]]);
+        assert!(app_secret(&s).unwrap_err().to_string().contains("failed"));
+
+        let err = app_secret(&settings(vec![])).unwrap_err();
+        assert!(err.to_string().contains("FEISHU_APP_SECRET_FILE"));
+    }
+}
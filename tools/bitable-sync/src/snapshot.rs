@@ -0,0 +1,342 @@
+//! Local per-table record cache for incremental sync.
+//!
+//! After a full read, each table's records are kept on disk together with a
+//! watermark (the newest `last_modified_time` seen). The next run lists only
+//! record ids and modification times, fetches records that are new or were
+//! modified at or after the watermark, and drops ids that disappeared.
+//!
+//! Formula and lookup columns change when the records they reference do,
+//! without touching the record's own modification time, so the listing
+//! carries them too and they are refreshed on every record each run.
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
+
+use crate::config::TableKind;
+use crate::feishu::bitable::{BitableClient, FieldType, RecordItem};
+use crate::feishu::search::SearchOptions;
+
+/// Records of one table as of the last successful sync
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TableSnapshot {
+    pub table_id: String,
+    /// The search query the snapshot was built with; a different view,
+    /// filter or projection invalidates it
+    pub query: String,
+    /// Newest `last_modified_time` among the records (ms since epoch)
+    pub watermark: i64,
+    pub records: Vec<RecordItem>,
+}
+
+/// What changed between a snapshot and the table
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct Delta {
+    pub added: usize,
+    pub changed: usize,
+    pub deleted: usize,
+}
+
+impl TableSnapshot {
+    pub fn new(table_id: &str, query: String, records: Vec<RecordItem>) -> Self {
+        Self {
+            table_id: table_id.to_string(),
+            query,
+            watermark: watermark(&records),
+            records,
+        }
+    }
+
+    /// Ids from `index` that must be (re)fetched: unknown to the snapshot,
+    /// or modified at or after the watermark
+    pub fn stale_ids(&self, index: &[RecordItem]) -> Vec<String> {
+        let known: HashSet<&str> = self.records.iter().map(|r| r.record_id.as_str()).collect();
+        index
+            .iter()
+            .filter(|r| {
+                !known.contains(r.record_id.as_str())
+                    || r.last_modified_time.is_none_or(|t| t >= self.watermark)
+            })
+            .map(|r| r.record_id.clone())
+            .collect()
+    }
+
+    /// Rebuild the snapshot in `index` order, preferring freshly fetched
+    /// records over cached ones. Ids missing from `index` were deleted.
+    pub fn merge(&self, index: &[RecordItem], fetched: Vec<RecordItem>) -> (TableSnapshot, Delta) {
+        let mut cached: HashMap<&str, &RecordItem> = self
+            .records
+            .iter()
+            .map(|r| (r.record_id.as_str(), r))
+            .collect();
+        let mut fetched: HashMap<String, RecordItem> = fetched
+            .into_iter()
+            .map(|r| (r.record_id.clone(), r))
+            .collect();
+
+        let mut delta = Delta::default();
+        let mut records = Vec::with_capacity(index.len());
+        for entry in index {
+            let old = cached.remove(entry.record_id.as_str());
+            let record = match (fetched.remove(&entry.record_id), old) {
+                (Some(new), Some(_)) => {
+                    delta.changed += 1;
+                    new
+                }
+                (Some(new), None) => {
+                    delta.added += 1;
+                    new
+                }
+                (None, Some(old)) => old.clone(),
+                // Deleted between listing and fetching
+                (None, None) => continue,
+            };
+            records.push(record);
+        }
+        delta.deleted = cached.len();
+
+        let snapshot = TableSnapshot::new(&self.table_id, self.query.clone(), records);
+        (snapshot, delta)
+    }
+
+    /// Copy `columns` from `index` onto every record it lists
+    pub fn refresh_columns(&mut self, index: &[RecordItem], columns: &[String]) {
+        let listed: HashMap<&str, &RecordItem> =
+            index.iter().map(|r| (r.record_id.as_str(), r)).collect();
+        for record in &mut self.records {
+            let Some(entry) = listed.get(record.record_id.as_str()) else {
+                continue;
+            };
+            for column in columns {
+                match entry.fields.get(column) {
+                    Some(value) => record.fields.insert(column.clone(), value.clone()),
+                    None => record.fields.remove(column),
+                };
+            }
+        }
+    }
+}
+
+/// Drop columns outside the query's projection (batch_get returns them all)
+fn project(records: &mut [RecordItem], field_names: Option<&Vec<String>>) {
+    let Some(names) = field_names else { return };
+    for record in records {
+        record.fields.retain(|name, _| names.contains(name));
+    }
+}
+
+fn watermark(records: &[RecordItem]) -> i64 {
+    records
+        .iter()
+        .filter_map(|r| r.last_modified_time)
+        .max()
+        .unwrap_or(0)
+}
+
+/// Snapshot files on disk, one JSON file per table
+pub struct SnapshotStore {
+    dir: PathBuf,
+}
+
+impl SnapshotStore {
+    pub fn new(dir: PathBuf) -> Self {
+        Self { dir }
+    }
+
+    fn path(&self, kind: TableKind) -> PathBuf {
+        self.dir
+            .join(format!("{}.json", kind.env_suffix().to_lowercase()))
+    }
+
+    /// Load a table's snapshot; a missing or unreadable file means a full read
+    pub fn load(&self, kind: TableKind) -> Option<TableSnapshot> {
+        let path = self.path(kind);
+        let content = std::fs::read_to_string(&path).ok()?;
+        match serde_json::from_str(&content) {
+            Ok(snapshot) => Some(snapshot),
+            Err(e) => {
+                tracing::warn!("Ignoring corrupt snapshot {}: {}", path.display(), e);
+                None
+            }
+        }
+    }
+
+    pub fn save(&self, kind: TableKind, snapshot: &TableSnapshot) -> Result<()> {
+        std::fs::create_dir_all(&self.dir)
+            .with_context(|| format!("Failed to create {}", self.dir.display()))?;
+        let path = self.path(kind);
+        // Write then rename so an interrupted run never leaves half a snapshot
+        let tmp = path.with_extension("json.tmp");
+        std::fs::write(&tmp, serde_json::to_vec(snapshot)?)
+            .with_context(|| format!("Failed to write {}", tmp.display()))?;
+        std::fs::rename(&tmp, &path)
+            .with_context(|| format!("Failed to write {}", path.display()))?;
+        Ok(())
+    }
+}
+
+/// Read a table through `query`, incrementally when `previous` matches the
+/// same table and query, in full otherwise
+pub async fn read_table(
+    client: &BitableClient,
+    table_id: &str,
+    query: &SearchOptions,
+    previous: Option<TableSnapshot>,
+) -> Result<TableSnapshot> {
+    let query_key = serde_json::to_string(query)?;
+    let previous = previous.filter(|s| s.table_id == table_id && s.query == query_key);
+
+    let Some(previous) = previous else {
+        let records = client
+            .search_records(table_id, &query.clone().automatic_fields())
+            .await?;
+        return Ok(TableSnapshot::new(table_id, query_key, records));
+    };
+
+    // List ids and modification times, projected to the primary field and
+    // the formula and lookup columns the query reads
+    let fields = client.list_fields(table_id).await?;
+    let primary = fields
+        .iter()
+        .find(|f| f.is_primary == Some(true))
+        .with_context(|| format!("Table {} has no primary field", table_id))?;
+    let computed: Vec<String> = fields
+        .iter()
+        .filter(|f| {
+            matches!(
+                FieldType::from_code(f.field_type),
+                Some(FieldType::Formula | FieldType::Lookup)
+            ) && f.field_name != primary.field_name
+                && query
+                    .field_names
+                    .as_ref()
+                    .is_none_or(|names| names.contains(&f.field_name))
+        })
+        .map(|f| f.field_name.clone())
+        .collect();
+    let mut index_query = query.clone().automatic_fields();
+    let mut index_columns = vec![primary.field_name.clone()];
+    index_columns.extend(computed.iter().cloned());
+    index_query.field_names = Some(index_columns);
+    let index = client.search_records(table_id, &index_query).await?;
+
+    let stale = previous.stale_ids(&index);
+    let mut fetched = if stale.is_empty() {
+        Vec::new()
+    } else {
+        client.batch_get_records(table_id, &stale).await?
+    };
+    project(&mut fetched, query.field_names.as_ref());
+
+    let (mut snapshot, delta) = previous.merge(&index, fetched);
+    snapshot.refresh_columns(&index, &computed);
+    tracing::info!(
+        "Table {}: {} added, {} changed, {} deleted since last sync",
+        table_id,
+        delta.added,
+        delta.changed,
+        delta.deleted
+    );
+    Ok(snapshot)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(id: &str, modified: i64, name: &str) -> RecordItem {
+        RecordItem {
+            record_id: id.to_string(),
+            fields: HashMap::from([("名称".to_string(), serde_json::json!(name))]),
+            last_modified_time: Some(modified),
+        }
+    }
+
+    fn index(entries: &[(&str, i64)]) -> Vec<RecordItem> {
+        entries
+            .iter()
+            .map(|(id, t)| RecordItem {
+                record_id: id.to_string(),
+                fields: HashMap::new(),
+                last_modified_time: Some(*t),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn merges_changes_and_detects_deletions() {
+        let snapshot = TableSnapshot::new(
+            "tblP",
+            "{}".to_string(),
+            vec![
+                record("rec1", 100, "a"),
+                record("rec2", 200, "b"),
+                record("rec3", 150, "c"),
+            ],
+        );
+        assert_eq!(snapshot.watermark, 200);
+
+        // rec2 edited, rec3 deleted, rec4 new
+        let listed = index(&[("rec1", 100), ("rec2", 300), ("rec4", 250)]);
+        assert_eq!(snapshot.stale_ids(&listed), vec!["rec2", "rec4"]);
+
+        let fetched = vec![record("rec2", 300, "b2"), record("rec4", 250, "d")];
+        let (merged, delta) = snapshot.merge(&listed, fetched);
+
+        assert_eq!(
+            delta,
+            Delta {
+                added: 1,
+                changed: 1,
+                deleted: 1
+            }
+        );
+        let names: Vec<_> = merged
+            .records
+            .iter()
+            .map(|r| r.fields["名称"].as_str().unwrap())
+            .collect();
+        assert_eq!(names, vec!["a", "b2", "d"]);
+        assert_eq!(merged.watermark, 300);
+    }
+
+    #[test]
+    fn computed_columns_follow_the_listing() {
+        let mut snapshot = TableSnapshot::new(
+            "tblP",
+            "{}".to_string(),
+            vec![record("rec1", 100, "a"), record("rec2", 100, "b")],
+        );
+        let mut listed = index(&[("rec1", 100), ("rec2", 100)]);
+        listed[0]
+            .fields
+            .insert("产地".to_string(), serde_json::json!("绍兴"));
+        snapshot.refresh_columns(&listed, &["产地".to_string()]);
+
+        assert_eq!(snapshot.records[0].fields["产地"], "绍兴");
+        assert!(!snapshot.records[1].fields.contains_key("产地"));
+        assert_eq!(snapshot.records[1].fields["名称"], "b");
+
+        let mut fetched = vec![record("rec3", 1, "c")];
+        fetched[0]
+            .fields
+            .insert("毛利率".to_string(), serde_json::json!(0.3));
+        project(&mut fetched, Some(&vec!["名称".to_string()]));
+        assert_eq!(fetched[0].fields.len(), 1);
+    }
+
+    #[test]
+    fn store_round_trips_snapshots() {
+        let dir = crate::testing::TempDir::new("state");
+        let store = SnapshotStore::new(dir.path().join("state"));
+        assert!(store.load(TableKind::Brands).is_none());
+
+        let snapshot = TableSnapshot::new("tblB", "{}".to_string(), vec![record("rec1", 5, "a")]);
+        store.save(TableKind::Brands, &snapshot).unwrap();
+
+        let loaded = store.load(TableKind::Brands).unwrap();
+        assert_eq!(loaded.watermark, 5);
+        assert_eq!(loaded.records[0].record_id, "rec1");
+    }
+}
@@ -1,16 +1,27 @@
 use anyhow::{Context, Result};
+use std::collections::{BTreeMap, HashMap};
 use std::path::Path;
 
-use crate::config::Config;
+use crate::columns::ColumnMap;
+use crate::config::{Config, TableKind};
 use crate::feishu::auth::FeishuAuth;
-use crate::feishu::bitable::BitableClient;
+use crate::feishu::bitable::{BitableClient, RecordItem};
+use crate::feishu::drive::DriveClient;
+use crate::feishu::search::{Condition, Filter, SearchOptions};
 use crate::models::bitable_records;
+use crate::models::bitable_value::RecordFields;
 use crate::models::mock_data::StoreInfo;
 use crate::models::product::Category;
+use crate::schema::Severity;
+use crate::snapshot::{read_table, SnapshotStore, TableSnapshot};
 
 pub struct SyncOptions {
     pub dry_run: bool,
     pub no_push: bool,
+    /// Re-read every table instead of diffing against local snapshots
+    pub full: bool,
+    /// Abort before writing anything when validation finds errors
+    pub strict: bool,
 }
 
 pub async fn run_sync(config: &Config, opts: &SyncOptions) -> Result<()> {
@@ -20,34 +31,86 @@ pub async fn run_sync(config: &Config, opts: &SyncOptions) -> Result<()> {
         .context("Config validation failed before sync")?;
 
     // 1. Initialize auth and client
-    let auth = FeishuAuth::new(config.feishu_app_id.clone(), config.feishu_app_secret.clone());
+    let auth = FeishuAuth::from_config(config);
     let client = BitableClient::new(auth.clone(), config.bitable_app_token.clone());
+    let drive = DriveClient::new(auth);
 
-    // 2. Read all tables concurrently
+    // 2. Read all tables concurrently (only changed records when a snapshot exists)
     tracing::info!("Reading all tables from bitable...");
-    let has_slogans_table = !config.table_id_slogans.is_empty();
-
-    let (products_raw, brands_raw, categories_raw, media_raw, store_raw) = tokio::try_join!(
-        client.read_all_records(&config.table_id_products),
-        client.read_all_records(&config.table_id_brands),
-        client.read_all_records(&config.table_id_display_categories),
-        client.read_all_records(&config.table_id_media),
-        client.read_all_records(&config.table_id_store_info),
-    )?;
+    let has_slogans_table = !config.table_id(TableKind::Slogans).is_empty();
+    let views = resolve_views(&client, config).await?;
+    let view = |kind: TableKind| views.get(&kind).map(|v| v.as_str());
+
+    // Which column each model field is read from, per table
+    let mut column_maps = BTreeMap::new();
+    for kind in TableKind::ALL {
+        if !config.table_id(kind).is_empty() {
+            column_maps.insert(kind, ColumnMap::load(&client, config, kind).await?);
+        }
+    }
+    let cols = |kind: TableKind| &column_maps[&kind];
+    let mut products_query = product_search(cols(TableKind::Products));
+    products_query.view_id = view(TableKind::Products).map(str::to_string);
 
-    let slogans_raw = if has_slogans_table {
-        client.read_all_records(&config.table_id_slogans).await?
-    } else {
-        tracing::warn!("TABLE_ID_SLOGANS not set, skipping slogans sync");
-        vec![]
+    let store = SnapshotStore::new(config.state_dir.clone());
+    if opts.full {
+        tracing::info!("Full sync requested, ignoring local snapshots");
+    }
+    let read = |kind: TableKind, query: SearchOptions| {
+        let previous = if opts.full { None } else { store.load(kind) };
+        let client = &client;
+        async move {
+            let snapshot = read_table(client, config.table_id(kind), &query, previous)
+                .await
+                .with_context(|| format!("Failed to read {:?} table", kind))?;
+            anyhow::Ok((kind, snapshot))
+        }
+    };
+    // Only the columns the parsers read
+    let view_query = |kind: TableKind| SearchOptions {
+        view_id: view(kind).map(str::to_string),
+        ..SearchOptions::default().fields(&cols(kind).present())
+    };
+
+    let slogans = async {
+        if has_slogans_table {
+            read(TableKind::Slogans, view_query(TableKind::Slogans))
+                .await
+                .map(Some)
+        } else {
+            tracing::warn!("TABLE_ID_SLOGANS not set, skipping slogans sync");
+            Ok(None)
+        }
     };
+    let (products, brands, categories, media, shop, slogans) = tokio::try_join!(
+        read(TableKind::Products, products_query),
+        read(TableKind::Brands, view_query(TableKind::Brands)),
+        read(TableKind::DisplayCategories, view_query(TableKind::DisplayCategories)),
+        read(TableKind::Media, view_query(TableKind::Media)),
+        read(TableKind::StoreInfo, view_query(TableKind::StoreInfo)),
+        slogans,
+    )?;
+    let snapshots: BTreeMap<TableKind, TableSnapshot> =
+        [products, brands, categories, media, shop]
+            .into_iter()
+            .chain(slogans)
+            .collect();
+    let records = |kind: TableKind| -> &[RecordItem] {
+        snapshots.get(&kind).map_or(&[], |s| s.records.as_slice())
+    };
+    let products_raw = records(TableKind::Products);
+    let brands_raw = records(TableKind::Brands);
+    let categories_raw = records(TableKind::DisplayCategories);
+    let media_raw = records(TableKind::Media);
+    let store_raw = records(TableKind::StoreInfo);
+    let slogans_raw = records(TableKind::Slogans);
 
     // 3. Parse records
     tracing::info!("Parsing records...");
 
     let brands: Vec<_> = brands_raw
         .iter()
-        .filter_map(|r| match bitable_records::parse_brand(&r.fields) {
+        .filter_map(|r| match bitable_records::parse_brand(&r.fields, cols(TableKind::Brands)) {
             Ok(b) => Some(b),
             Err(e) => {
                 tracing::warn!("Skipping brand record: {}", e);
@@ -59,7 +122,7 @@ pub async fn run_sync(config: &Config, opts: &SyncOptions) -> Result<()> {
 
     let display_categories: Vec<_> = categories_raw
         .iter()
-        .filter_map(|r| match bitable_records::parse_display_category(&r.fields) {
+        .filter_map(|r| match bitable_records::parse_display_category(&r.fields, cols(TableKind::DisplayCategories)) {
             Ok(c) => Some(c),
             Err(e) => {
                 tracing::warn!("Skipping category record: {}", e);
@@ -71,7 +134,7 @@ pub async fn run_sync(config: &Config, opts: &SyncOptions) -> Result<()> {
 
     let slogans: Vec<_> = slogans_raw
         .iter()
-        .filter_map(|r| match bitable_records::parse_slogan(&r.fields) {
+        .filter_map(|r| match bitable_records::parse_slogan(&r.fields, cols(TableKind::Slogans)) {
             Ok(s) => Some(s),
             Err(e) => {
                 tracing::debug!("Skipping slogan record: {}", e);
@@ -83,7 +146,7 @@ pub async fn run_sync(config: &Config, opts: &SyncOptions) -> Result<()> {
 
     let raw_media_items: Vec<_> = media_raw
         .iter()
-        .filter_map(|r| match crate::video::parse_raw_media_item(&r.fields) {
+        .filter_map(|r| match crate::video::parse_raw_media_item(&r.fields, cols(TableKind::Media)) {
             Ok(m) => Some(m),
             Err(e) => {
                 tracing::warn!("Skipping media record: {}", e);
@@ -93,6 +156,38 @@ pub async fn run_sync(config: &Config, opts: &SyncOptions) -> Result<()> {
         .collect();
     tracing::info!("Parsed {} media items", raw_media_items.len());
 
+    let mut raw_products: Vec<_> = products_raw
+        .iter()
+        .filter_map(|r| match bitable_records::parse_raw_product(&r.fields, cols(TableKind::Products)) {
+            Ok(p) => Some(p),
+            Err(e) => {
+                tracing::warn!("Skipping product record: {}", e);
+                None
+            }
+        })
+        .collect();
+    tracing::info!("Parsed {} products", raw_products.len());
+
+    // Link cells from search carry only record_ids: name the linked brand
+    // and category by their ids instead
+    let brand_ids = ids_by_record(brands_raw, cols(TableKind::Brands));
+    let category_ids = ids_by_record(categories_raw, cols(TableKind::DisplayCategories));
+    for product in &mut raw_products {
+        if let Some(id) = product.brand_record_id.as_ref().and_then(|r| brand_ids.get(r)) {
+            product.brand_id_link = Some(id.clone());
+        }
+        if let Some(id) = product.category_record_id.as_ref().and_then(|r| category_ids.get(r)) {
+            product.category_id_link = Some(id.clone());
+        }
+    }
+
+    // 3a. Validate before anything is downloaded or written
+    let report = crate::validate::validate(&brands, &display_categories, &raw_products);
+    report.log();
+    if opts.strict {
+        report.ensure_no_errors()?;
+    }
+
     // 3b. Process video attachments: download from Feishu -> ffmpeg HLS -> public/videos/
     let media_items = if opts.dry_run {
         // In dry-run mode, skip video downloads and just use placeholder URLs
@@ -111,12 +206,12 @@ pub async fn run_sync(config: &Config, opts: &SyncOptions) -> Result<()> {
             })
             .collect()
     } else {
-        crate::video::process_media_items(&auth, raw_media_items, &config.public_dir()).await?
+        crate::video::process_media_items(&drive, raw_media_items, &config.public_dir()).await?
     };
 
     let mut store_info = store_raw
         .first()
-        .map(|r| bitable_records::parse_store_info(&r.fields))
+        .map(|r| bitable_records::parse_store_info(&r.fields, cols(TableKind::StoreInfo)))
         .transpose()?
         .unwrap_or(StoreInfo {
             name: "绍兴黄酒专卖".to_string(),
@@ -129,7 +224,7 @@ pub async fn run_sync(config: &Config, opts: &SyncOptions) -> Result<()> {
     if !opts.dry_run {
         if let Some(ref token) = store_info.qr_file_token {
             let qr_dest = config.public_dir().join("images").join("qrcode.jpg");
-            match crate::video::download_image_attachment(&auth, token, &qr_dest).await {
+            match crate::video::download_image_attachment(&drive, token, &qr_dest).await {
                 Ok(()) => {
                     store_info.qr_code_url = "images/qrcode.jpg".to_string();
                     tracing::info!("QR code downloaded to public/images/qrcode.jpg");
@@ -154,18 +249,6 @@ pub async fn run_sync(config: &Config, opts: &SyncOptions) -> Result<()> {
 
     tracing::info!("Store info: {}", store_info.name);
 
-    let mut raw_products: Vec<_> = products_raw
-        .iter()
-        .filter_map(|r| match bitable_records::parse_raw_product(&r.fields) {
-            Ok(p) => Some(p),
-            Err(e) => {
-                tracing::warn!("Skipping product record: {}", e);
-                None
-            }
-        })
-        .collect();
-    tracing::info!("Parsed {} products", raw_products.len());
-
     // T004: Download product images from Feishu attachments to public/images/products/
     if !opts.dry_run {
         let products_img_dir = config.public_dir().join("images").join("products");
@@ -173,7 +256,7 @@ pub async fn run_sync(config: &Config, opts: &SyncOptions) -> Result<()> {
             if let Some(ref token) = product.main_image_file_token {
                 let dest = products_img_dir.join(format!("{}.jpg", product.id));
                 if !dest.exists() {
-                    match crate::video::download_image_attachment(&auth, token, &dest).await {
+                    match crate::video::download_image_attachment(&drive, token, &dest).await {
                         Ok(()) => {
                             tracing::info!("Downloaded product image for '{}'", product.name);
                         }
@@ -252,10 +335,15 @@ pub async fn run_sync(config: &Config, opts: &SyncOptions) -> Result<()> {
     crate::output::json_writer::write_product_database(&product_db, &json_path)?;
     crate::output::ts_writer::write_mock_data_ts(&mock_data, &ts_path)?;
 
+    // Files reflect these snapshots now; the next run diffs against them
+    for (kind, snapshot) in &snapshots {
+        store.save(*kind, snapshot)?;
+    }
+
     // 7. Validate image paths
     validate_image_paths(&config.repo_root, &product_db, &mock_data)?;
 
-    if opts.no_push {
+    if opts.no_push || !config.push {
         tracing::info!("No-push mode - files written but not committed");
         return Ok(());
     }
@@ -297,6 +385,57 @@ pub async fn run_sync(config: &Config, opts: &SyncOptions) -> Result<()> {
     Ok(())
 }
 
+/// The `id` column of each record, by record_id
+fn ids_by_record(records: &[RecordItem], cols: &ColumnMap) -> HashMap<String, String> {
+    records
+        .iter()
+        .filter_map(|r| {
+            let id = RecordFields::new(&r.fields).text(cols.get("id")).ok()??;
+            Some((r.record_id.clone(), id))
+        })
+        .collect()
+}
+
+/// Resolve each table's configured view (name or id) to a view_id
+async fn resolve_views(
+    client: &BitableClient,
+    config: &Config,
+) -> Result<BTreeMap<TableKind, String>> {
+    let mut views = BTreeMap::new();
+    for (kind, table) in &config.tables {
+        if let (Some(view), false) = (&table.view, table.id.is_empty()) {
+            let view_id = client
+                .resolve_view_id(&table.id, view)
+                .await
+                .with_context(|| format!("VIEW_{} is invalid", kind.env_suffix()))?;
+            tracing::info!("Reading {:?} through view '{}' ({})", kind, view, view_id);
+            views.insert(*kind, view_id);
+        }
+    }
+    Ok(views)
+}
+
+/// Only fetch products that can be published: status `active`, or blank
+/// (which `parse_raw_product` treats as active), projected to parsed columns.
+///
+/// Search rejects unknown field names, so the projection is limited to the
+/// columns the table actually has (older bases lack e.g. 上架日期).
+fn product_search(cols: &ColumnMap) -> SearchOptions {
+    let fields = cols.present();
+
+    let status = cols.get("status");
+    let query = SearchOptions::default().fields(&fields);
+    if fields.contains(&status) {
+        query.filter(
+            Filter::or()
+                .condition(Condition::is(status, "active"))
+                .condition(Condition::is_empty(status)),
+        )
+    } else {
+        query
+    }
+}
+
 fn validate_image_paths(
     repo_path: &Path,
     db: &crate::models::product::ProductDatabase,
@@ -352,7 +491,7 @@ pub async fn check_config(config: &Config) -> Result<()> {
     tracing::info!("Repository path OK: {}", config.repo_root.display());
 
     // Test auth
-    let auth = FeishuAuth::new(config.feishu_app_id.clone(), config.feishu_app_secret.clone());
+    let auth = FeishuAuth::from_config(config);
     let token = auth.get_token().await.context("Auth failed")?;
     tracing::info!("Auth OK (token: {}...)", &token[..8.min(token.len())]);
 
@@ -364,18 +503,63 @@ pub async fn check_config(config: &Config) -> Result<()> {
         tracing::info!("  - {} ({})", t.name, t.table_id);
     }
 
-    tracing::info!("All checks passed!");
+    // Compare every configured table with the schema the parsers expect
+    let schemas = crate::schema::define_schemas();
+    let table_ids: BTreeMap<TableKind, String> = config
+        .tables
+        .iter()
+        .map(|(kind, t)| (*kind, t.id.clone()))
+        .collect();
+    let mut errors = 0;
+    let mut warnings = 0;
+
+    for schema in &schemas {
+        let Some(kind) = schema.kind else { continue };
+        let table_id = config.table_id(kind);
+        if table_id.is_empty() {
+            continue;
+        }
+        let fields = client
+            .list_fields(table_id)
+            .await
+            .with_context(|| format!("Failed to list fields of {:?} table", kind))?;
+        let cols = ColumnMap::for_fields(config, kind, &fields);
+        let findings = crate::schema::diff_table(schema, &schemas, &fields, &cols, &table_ids);
+
+        println!("\n{} ({})", schema.name, table_id);
+        if findings.iter().all(|f| f.severity == Severity::Info) {
+            println!("  OK");
+        }
+        for f in &findings {
+            let mark = match f.severity {
+                Severity::Error => "ERROR",
+                Severity::Warning => "WARN ",
+                Severity::Info => "info ",
+            };
+            println!("  {} {}: {}", mark, f.field, f.message);
+        }
+        errors += findings.iter().filter(|f| f.severity == Severity::Error).count();
+        warnings += findings.iter().filter(|f| f.severity == Severity::Warning).count();
+    }
+
+    anyhow::ensure!(
+        errors == 0,
+        "Schema check failed: {} errors, {} warnings",
+        errors,
+        warnings
+    );
+    tracing::info!("All checks passed! ({} schema warnings)", warnings);
     Ok(())
 }
 
 /// List all tables in the bitable app
 pub async fn list_tables(config: &Config) -> Result<()> {
-    let auth = FeishuAuth::new(config.feishu_app_id.clone(), config.feishu_app_secret.clone());
+    let auth = FeishuAuth::from_config(config);
     let client = BitableClient::new(auth, config.bitable_app_token.clone());
 
     let tables = client.list_tables().await?;
     println!("Tables in bitable app ({}):", config.bitable_app_token);
-    println!("{:<30} {}", "Name", "Table ID");
+    println!("{:<30} Table ID", "Name");
     println!("{}", "-".repeat(60));
     for t in &tables {
         println!("{:<30} {}", t.name, t.table_id);
@@ -383,3 +567,150 @@ pub async fn list_tables(config: &Config) -> Result<()> {
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::feishu::stub::{token_response, StubResponse, StubServer};
+    use crate::profile::{Settings, Source};
+    use crate::testing::TempDir;
+    use serde_json::json;
+
+    fn text(s: &str) -> serde_json::Value {
+        json!([{"type": "text", "text": s}])
+    }
+
+    /// A Feishu app with one brand, one category and `products`, records
+    /// shaped as the search endpoint returns them
+    async fn stub_app(products: Vec<serde_json::Value>) -> StubServer {
+        StubServer::start(move |req| {
+            if req.path.starts_with("/auth/") {
+                return token_response("t", 7200);
+            }
+            let table = req.path.split("/tables/").nth(1).unwrap_or("");
+            let table = table.split(['/', '?']).next().unwrap();
+            let (columns, items) = match table {
+                "tblP" => (
+                    vec!["商品ID", "商品名称", "零售价", "品牌", "分类", "展示分类", "状态"],
+                    products.clone(),
+                ),
+                "tblB" => (
+                    vec!["品牌ID", "品牌名称"],
+                    vec![json!({"record_id": "recB1", "fields": {
+                        "品牌ID": text("brand_gyl"), "品牌名称": text("古越龙山")
+                    }})],
+                ),
+                "tblC" => (
+                    vec!["分类ID", "分类名称"],
+                    vec![json!({"record_id": "recC1", "fields": {
+                        "分类ID": text("hot"), "分类名称": text("热销")
+                    }})],
+                ),
+                _ => (vec![], vec![]),
+            };
+            let items = if req.path.contains("/fields") {
+                columns
+                    .iter()
+                    .enumerate()
+                    .map(|(i, name)| {
+                        json!({"field_id": format!("fld{}", i), "field_name": name, "type": 1, "is_primary": i == 0})
+                    })
+                    .collect()
+            } else {
+                items
+            };
+            StubResponse::json(json!({"code": 0, "msg": "ok", "data": {"has_more": false, "items": items}}))
+        })
+        .await
+    }
+
+    /// A site checkout with just what `Config::validate` looks for
+    fn site() -> TempDir {
+        let dir = TempDir::new("site");
+        for sub in [".git", "src/data", "public"] {
+            std::fs::create_dir_all(dir.path().join(sub)).unwrap();
+        }
+        std::fs::write(dir.path().join("package.json"), "{}").unwrap();
+        dir
+    }
+
+    fn config(server: &StubServer, site: &TempDir) -> Config {
+        let site = site.path().to_str().unwrap();
+        let vars = [
+            ("FEISHU_BASE_URL", server.base_url.as_str()),
+            ("FEISHU_APP_ID", "app"),
+            ("FEISHU_APP_SECRET", "secret"),
+            ("BITABLE_APP_TOKEN", "app_token"),
+            ("TABLE_ID_PRODUCTS", "tblP"),
+            ("TABLE_ID_BRANDS", "tblB"),
+            ("TABLE_ID_DISPLAY_CATEGORIES", "tblC"),
+            ("TABLE_ID_MEDIA", "tblM"),
+            ("TABLE_ID_STORE_INFO", "tblS"),
+            ("FAMILY_BUSINESS_REPO", site),
+        ];
+        let vars = vars
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+        Config::load(Settings::from_layers(vec![(Source::Env, vars)])).unwrap()
+    }
+
+    fn options() -> SyncOptions {
+        SyncOptions {
+            dry_run: false,
+            no_push: true,
+            full: false,
+            strict: false,
+        }
+    }
+
+    #[tokio::test]
+    async fn strict_sync_stops_on_an_unknown_category() {
+        let server = stub_app(vec![json!({"record_id": "recP1", "fields": {
+            "商品ID": text("P001"),
+            "商品名称": text("花雕"),
+            "零售价": 100,
+            "品牌": {"link_record_ids": ["recB1"]},
+            "分类": {"link_record_ids": ["recC1"]},
+            "展示分类": "hot,gift",
+        }})])
+        .await;
+        let site = site();
+        let config = config(&server, &site);
+        let opts = SyncOptions {
+            strict: true,
+            ..options()
+        };
+
+        let err = run_sync(&config, &opts).await.unwrap_err();
+
+        assert!(err.to_string().contains("found 1 errors; nothing was written"));
+        assert!(!config.data_dir().join("productDatabase.json").exists());
+        assert!(!config.data_dir().join("mockData.ts").exists());
+        assert!(!config.state_dir.exists());
+    }
+
+    #[tokio::test]
+    async fn links_from_search_resolve_by_record_id() {
+        let server = stub_app(vec![json!({"record_id": "recP1", "fields": {
+            "商品ID": text("P001"),
+            "商品名称": text("花雕"),
+            "零售价": 100,
+            "品牌": {"link_record_ids": ["recB1"]},
+            "分类": {"link_record_ids": ["recC1"]},
+            "状态": "active",
+        }})])
+        .await;
+        let site = site();
+        let config = config(&server, &site);
+
+        run_sync(&config, &options()).await.unwrap();
+
+        let db = std::fs::read_to_string(config.data_dir().join("productDatabase.json")).unwrap();
+        let db: serde_json::Value = serde_json::from_str(&db).unwrap();
+        let product = &db["products"][0];
+        assert_eq!(product["brand"]["id"], "brand_gyl");
+        assert_eq!(product["brand"]["name"], "古越龙山");
+        assert_eq!(product["category"]["id"], "hot");
+    }
+}
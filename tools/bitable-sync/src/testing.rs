@@ -0,0 +1,25 @@
+//! Helpers shared by tests.
+
+use std::path::{Path, PathBuf};
+
+/// A fresh directory under the system temp dir, removed on drop
+pub struct TempDir(PathBuf);
+
+impl TempDir {
+    pub fn new(prefix: &str) -> Self {
+        let path =
+            std::env::temp_dir().join(format!("bitable-sync-{}-{}", prefix, fastrand::u64(..)));
+        std::fs::create_dir_all(&path).unwrap();
+        Self(path)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
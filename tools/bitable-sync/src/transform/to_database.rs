@@ -67,6 +67,13 @@ pub fn build_product_database(
                     icon: None,
                 });
 
+            // Product's own 产地 (often a lookup of the brand), then the brand's
+            let origin = raw
+                .origin
+                .clone()
+                .or_else(|| brand.origin.clone())
+                .unwrap_or_else(|| "浙江绍兴".to_string());
+
             // Estimate weight from specification
             let weight = parse_weight(&raw.specification);
 
@@ -88,7 +95,7 @@ pub fn build_product_database(
                 stock: raw.stock,
                 safety_stock: (raw.stock as f64 * 0.25) as i32, // Default 25% safety stock
                 warehouse_location: None,
-                origin: "浙江绍兴".to_string(),
+                origin,
                 shelf_life: 36,
                 storage_condition: "阴凉干燥处保存".to_string(),
                 alcohol_content: raw.alcohol_content,
@@ -106,8 +113,8 @@ pub fn build_product_database(
                 is_hot: raw.is_hot,
                 is_new: raw.is_new,
                 is_promotion: raw.is_promotion,
-                created_at: now.clone(),
-                updated_at: now.clone(),
+                created_at: raw.listed_at.map(format_timestamp).unwrap_or_else(|| now.clone()),
+                updated_at: raw.updated_at.map(format_timestamp).unwrap_or_else(|| now.clone()),
             }
         })
         .collect();
@@ -122,6 +129,10 @@ pub fn build_product_database(
     })
 }
 
+fn format_timestamp(t: chrono::DateTime<chrono::Utc>) -> String {
+    t.format("%Y-%m-%dT%H:%M:%SZ").to_string()
+}
+
 /// Parse weight in ml from specification string like "500ml" or "2.5L"
 fn parse_weight(spec: &str) -> i32 {
     let lower = spec.to_lowercase();
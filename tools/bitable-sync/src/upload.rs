@@ -0,0 +1,195 @@
+use anyhow::{Context, Result};
+use std::collections::HashMap;
+use std::path::{Component, Path, PathBuf};
+
+use crate::columns::ColumnMap;
+use crate::config::{Config, TableKind};
+use crate::feishu::auth::FeishuAuth;
+use crate::feishu::bitable::{BitableClient, RecordUpdate};
+use crate::feishu::drive::{DriveClient, MediaParent};
+use crate::feishu::search::SearchOptions;
+use crate::models::bitable_value::RecordFields;
+use crate::models::product::ProductDatabase;
+
+const IMAGE_EXTENSIONS: &[&str] = &["jpg", "jpeg", "png", "webp"];
+/// Uploaded images are attached in batches this size, so a failure part way
+/// leaves few uploads unattached
+const ATTACH_BATCH: usize = 20;
+
+pub struct UploadOptions {
+    /// Only print which images would be uploaded
+    pub dry_run: bool,
+    /// Replace images that are already set in bitable
+    pub overwrite: bool,
+}
+
+/// Push product images from public/images/products back into 商品主图.
+///
+/// Products are matched by 商品ID: first through `mainImage` in
+/// productDatabase.json, then by a file named after the id (what sync writes).
+pub async fn upload_product_images(config: &Config, opts: &UploadOptions) -> Result<()> {
+    let auth = FeishuAuth::from_config(config);
+    let client = BitableClient::new(auth.clone(), config.bitable_app_token.clone());
+    let drive = DriveClient::new(auth);
+    let table_id = config.table_id(TableKind::Products);
+
+    let main_images = load_main_images(&config.data_dir().join("productDatabase.json"))?;
+    let cols = ColumnMap::load(&client, config, TableKind::Products).await?;
+    let (id_field, image_field) = (cols.get("id"), cols.get("main_image"));
+    let query = SearchOptions::default().fields(&[id_field, image_field]);
+    let records = client.search_records(table_id, &query).await?;
+
+    let public_dir = config.public_dir();
+    let mut updates = Vec::new();
+    let mut missing = Vec::new();
+    let mut failed = Vec::new();
+    let mut skipped = 0;
+    let mut planned = 0;
+    let mut attached = 0;
+
+    for record in &records {
+        let f = RecordFields::new(&record.fields);
+        let Some(id) = f.text(id_field)? else {
+            continue;
+        };
+        if !opts.overwrite && f.attachment(image_field)?.is_some() {
+            skipped += 1;
+            continue;
+        }
+        let Some(path) = find_image(&public_dir, &id, main_images.get(&id)) else {
+            missing.push(id);
+            continue;
+        };
+
+        planned += 1;
+        if opts.dry_run {
+            println!("{:<12} <- {}", id, path.display());
+            continue;
+        }
+
+        let file_token = match drive
+            .upload_file(&path, MediaParent::BitableImage, &config.bitable_app_token)
+            .await
+        {
+            Ok(token) => token,
+            Err(e) => {
+                tracing::error!("Failed to upload image for product {}: {:#}", id, e);
+                failed.push(id);
+                continue;
+            }
+        };
+        tracing::info!("Uploaded {} for product {}", path.display(), id);
+
+        updates.push(RecordUpdate {
+            record_id: record.record_id.clone(),
+            fields: HashMap::from([(
+                image_field.to_string(),
+                serde_json::json!([{"file_token": file_token}]),
+            )]),
+        });
+        if updates.len() >= ATTACH_BATCH {
+            client.batch_update_records(table_id, &updates).await?;
+            attached += updates.len();
+            updates.clear();
+        }
+    }
+
+    if !updates.is_empty() {
+        client.batch_update_records(table_id, &updates).await?;
+        attached += updates.len();
+    }
+
+    if !missing.is_empty() {
+        tracing::warn!("No local image for products: {}", missing.join(", "));
+    }
+    if opts.dry_run {
+        println!(
+            "Would upload {} images, {} already set, {} without a local file",
+            planned,
+            skipped,
+            missing.len()
+        );
+    } else {
+        println!(
+            "Uploaded {} images, {} already set, {} without a local file, {} failed",
+            attached,
+            skipped,
+            missing.len(),
+            failed.len()
+        );
+    }
+    anyhow::ensure!(
+        failed.is_empty(),
+        "Failed to upload images for products: {}",
+        failed.join(", ")
+    );
+    Ok(())
+}
+
+/// Map product id -> mainImage from productDatabase.json (empty if absent)
+fn load_main_images(path: &Path) -> Result<HashMap<String, String>> {
+    if !path.exists() {
+        return Ok(HashMap::new());
+    }
+    let content = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read {}", path.display()))?;
+    let db: ProductDatabase = serde_json::from_str(&content)
+        .with_context(|| format!("Failed to parse {}", path.display()))?;
+    Ok(db
+        .products
+        .into_iter()
+        .filter(|p| !p.main_image.is_empty())
+        .map(|p| (p.id, p.main_image))
+        .collect())
+}
+
+/// Find the local image for a product under public/images/products
+fn find_image(public_dir: &Path, id: &str, main_image: Option<&String>) -> Option<PathBuf> {
+    let products_dir = public_dir.join("images").join("products");
+
+    // `starts_with` compares components as written, so `..` could climb out
+    let from_db = main_image
+        .map(|m| Path::new(m.trim_start_matches('/')))
+        .filter(|m| m.components().all(|c| matches!(c, Component::Normal(_))))
+        .map(|m| public_dir.join(m))
+        .filter(|p| p.starts_with(&products_dir));
+    let by_id = IMAGE_EXTENSIONS
+        .iter()
+        .map(|ext| products_dir.join(format!("{}.{}", id, ext)));
+
+    from_db.into_iter().chain(by_id).find(|p| p.is_file())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TempDir;
+
+    #[test]
+    fn finds_images_by_main_image_then_id() {
+        let dir = TempDir::new("upload");
+        let public = dir.path();
+        let products = public.join("images").join("products");
+        std::fs::create_dir_all(&products).unwrap();
+        std::fs::write(products.join("gyl_huadiao_5y.png"), b"png").unwrap();
+        std::fs::write(products.join("P002.jpg"), b"jpg").unwrap();
+
+        let main = "/images/products/gyl_huadiao_5y.png".to_string();
+        assert_eq!(
+            find_image(public, "P001", Some(&main)),
+            Some(products.join("gyl_huadiao_5y.png"))
+        );
+        assert_eq!(
+            find_image(public, "P002", None),
+            Some(products.join("P002.jpg"))
+        );
+        assert_eq!(find_image(public, "P003", None), None);
+
+        // Only files under images/products are pushed back
+        let outside = "images/brands/logo.png".to_string();
+        assert_eq!(find_image(public, "P003", Some(&outside)), None);
+        std::fs::write(public.join("secret.png"), b"png").unwrap();
+        let escape = "images/products/../../secret.png".to_string();
+        assert_eq!(find_image(public, "P003", Some(&escape)), None);
+    }
+}
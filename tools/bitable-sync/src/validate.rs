@@ -0,0 +1,304 @@
+//! Checks on the parsed records before anything is written: links that
+//! don't resolve, duplicate ids, implausible prices, missing images.
+//!
+//! Sync logs every issue. With `--strict` any error aborts it before the
+//! first file is written; warnings never do.
+
+use anyhow::Result;
+use std::collections::{HashMap, HashSet};
+
+use crate::models::bitable_records::RawProduct;
+use crate::models::mock_data::DisplayCategory;
+use crate::models::product::Brand;
+use crate::schema::Severity;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Rule {
+    /// Two records of a table share an id
+    DuplicateId,
+    /// A product's brand is empty or not in the brands table (published as 未知品牌)
+    UnresolvedBrand,
+    /// A product's category is empty or not in the categories table (published as 未分类)
+    UnresolvedCategory,
+    /// A 展示分类 id that no category has
+    UnknownDisplayCategory,
+    /// No retail price (published as 0)
+    MissingPrice,
+    /// Promotion price above the retail price
+    PromotionAboveRetail,
+    /// Cost price above the retail price
+    CostAboveRetail,
+    /// No main image
+    MissingImage,
+}
+
+impl Rule {
+    pub fn severity(self) -> Severity {
+        match self {
+            Rule::DuplicateId
+            | Rule::UnresolvedBrand
+            | Rule::UnresolvedCategory
+            | Rule::UnknownDisplayCategory
+            | Rule::MissingPrice
+            | Rule::PromotionAboveRetail => Severity::Error,
+            Rule::CostAboveRetail | Rule::MissingImage => Severity::Warning,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Issue {
+    pub rule: Rule,
+    /// Which record, e.g. "product P001"
+    pub record: String,
+    pub message: String,
+}
+
+#[derive(Debug, Default)]
+pub struct Report {
+    pub issues: Vec<Issue>,
+}
+
+impl Report {
+    fn add(&mut self, rule: Rule, record: String, message: String) {
+        self.issues.push(Issue {
+            rule,
+            record,
+            message,
+        });
+    }
+
+    fn count(&self, severity: Severity) -> usize {
+        self.issues
+            .iter()
+            .filter(|i| i.rule.severity() == severity)
+            .count()
+    }
+
+    pub fn log(&self) {
+        for issue in &self.issues {
+            match issue.rule.severity() {
+                Severity::Error => tracing::error!("{}: {}", issue.record, issue.message),
+                _ => tracing::warn!("{}: {}", issue.record, issue.message),
+            }
+        }
+        if self.issues.is_empty() {
+            tracing::info!("Validation passed");
+        } else {
+            tracing::info!(
+                "Validation: {} errors, {} warnings",
+                self.count(Severity::Error),
+                self.count(Severity::Warning)
+            );
+        }
+    }
+
+    /// Fail when any issue is an error (for `--strict`)
+    pub fn ensure_no_errors(&self) -> Result<()> {
+        let errors = self.count(Severity::Error);
+        anyhow::ensure!(
+            errors == 0,
+            "Validation found {} errors; nothing was written (drop --strict to publish anyway)",
+            errors
+        );
+        Ok(())
+    }
+}
+
+/// Run every rule over what sync is about to publish (active products only)
+pub fn validate(
+    brands: &[Brand],
+    categories: &[DisplayCategory],
+    products: &[RawProduct],
+) -> Report {
+    let mut report = Report::default();
+    let products: Vec<&RawProduct> = products.iter().filter(|p| p.status == "active").collect();
+
+    duplicates(&mut report, "brand", brands.iter().map(|b| b.id.as_str()));
+    duplicates(
+        &mut report,
+        "category",
+        categories.iter().map(|c| c.id.as_str()),
+    );
+    duplicates(
+        &mut report,
+        "product",
+        products.iter().map(|p| p.id.as_str()),
+    );
+
+    // Links resolve by id or name, as in `build_product_database`
+    let brand_keys: HashSet<&str> = brands
+        .iter()
+        .flat_map(|b| [b.id.as_str(), b.name.as_str()])
+        .collect();
+    let category_keys: HashSet<&str> = categories
+        .iter()
+        .flat_map(|c| [c.id.as_str(), c.name.as_str()])
+        .collect();
+    let category_ids: HashSet<&str> = categories.iter().map(|c| c.id.as_str()).collect();
+
+    for p in products {
+        let record = format!("product {}", p.id);
+        let mut add = |rule, message: String| report.add(rule, record.clone(), message);
+
+        match p.brand_id_link.as_deref() {
+            None => add(Rule::UnresolvedBrand, "has no brand".to_string()),
+            Some(brand) if !brand_keys.contains(brand) => add(
+                Rule::UnresolvedBrand,
+                format!("brand '{}' does not exist", brand),
+            ),
+            Some(_) => {}
+        }
+        match p.category_id_link.as_deref() {
+            None => add(Rule::UnresolvedCategory, "has no category".to_string()),
+            Some(category) if !category_keys.contains(category) => add(
+                Rule::UnresolvedCategory,
+                format!("category '{}' does not exist", category),
+            ),
+            Some(_) => {}
+        }
+        for id in &p.display_category_ids {
+            if !category_ids.contains(id.as_str()) {
+                add(
+                    Rule::UnknownDisplayCategory,
+                    format!("display category '{}' does not exist", id),
+                );
+            }
+        }
+
+        if p.retail_price <= 0.0 {
+            add(Rule::MissingPrice, "has no retail price".to_string());
+        } else {
+            if let Some(promo) = p.promotion_price.filter(|&promo| promo > p.retail_price) {
+                add(
+                    Rule::PromotionAboveRetail,
+                    format!(
+                        "promotion price {} is above retail {}",
+                        promo, p.retail_price
+                    ),
+                );
+            }
+            if let Some(cost) = p.cost_price.filter(|&cost| cost > p.retail_price) {
+                add(
+                    Rule::CostAboveRetail,
+                    format!("cost price {} is above retail {}", cost, p.retail_price),
+                );
+            }
+        }
+
+        if p.main_image.is_empty() {
+            add(Rule::MissingImage, "has no main image".to_string());
+        }
+    }
+    report
+}
+
+fn duplicates<'a>(report: &mut Report, what: &str, ids: impl Iterator<Item = &'a str>) {
+    let mut seen: HashMap<&str, usize> = HashMap::new();
+    for id in ids {
+        *seen.entry(id).or_default() += 1;
+    }
+    let mut repeated: Vec<_> = seen.into_iter().filter(|(_, n)| *n > 1).collect();
+    repeated.sort();
+    for (id, n) in repeated {
+        report.add(
+            Rule::DuplicateId,
+            format!("{} {}", what, id),
+            format!("id is used by {} records", n),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::columns::ColumnMap;
+    use crate::config::TableKind;
+    use crate::models::bitable_records::parse_raw_product;
+    use serde_json::json;
+
+    fn product(id: &str, extra: &[(&str, serde_json::Value)]) -> RawProduct {
+        let mut fields = std::collections::HashMap::from([
+            ("商品ID".to_string(), json!(id)),
+            ("商品名称".to_string(), json!("花雕")),
+            ("零售价".to_string(), json!(100)),
+            (
+                "商品主图".to_string(),
+                json!([{"file_token": "box", "tmp_url": "https://t"}]),
+            ),
+            (
+                "品牌".to_string(),
+                json!([{"record_ids": ["rec1"], "text": "brand_gyl", "type": "text"}]),
+            ),
+            (
+                "分类".to_string(),
+                json!([{"record_ids": ["rec2"], "text": "hot", "type": "text"}]),
+            ),
+        ]);
+        for (k, v) in extra {
+            fields.insert(k.to_string(), v.clone());
+        }
+        let cols = ColumnMap::resolve(TableKind::Products, &Default::default(), &[]);
+        parse_raw_product(&fields, &cols).unwrap()
+    }
+
+    #[test]
+    fn rules_flag_bad_products() {
+        let brands = vec![Brand {
+            id: "brand_gyl".to_string(),
+            name: "古越龙山".to_string(),
+            logo: None,
+            story: None,
+            founded_year: None,
+            origin: None,
+        }];
+        let categories = vec![DisplayCategory {
+            id: "hot".to_string(),
+            name: "热销".to_string(),
+            icon: None,
+            sort_order: 0,
+        }];
+        let products = vec![
+            product("P001", &[]),
+            product("P002", &[("零售价", json!(0))]),
+            product("P002", &[("促销价", json!(120)), ("成本价", json!(150))]),
+            product(
+                "P003",
+                &[
+                    (
+                        "品牌",
+                        json!([{"record_ids": ["rec3"], "text": "brand_x", "type": "text"}]),
+                    ),
+                    ("展示分类", json!("hot,gift")),
+                    ("商品主图", json!([])),
+                ],
+            ),
+            // Not published, so not checked
+            product("P004", &[("零售价", json!(0)), ("状态", json!("inactive"))]),
+        ];
+
+        let report = validate(&brands, &categories, &products);
+        let rules: Vec<(Rule, &str)> = report
+            .issues
+            .iter()
+            .map(|i| (i.rule, i.record.as_str()))
+            .collect();
+        assert_eq!(
+            rules,
+            vec![
+                (Rule::DuplicateId, "product P002"),
+                (Rule::MissingPrice, "product P002"),
+                (Rule::PromotionAboveRetail, "product P002"),
+                (Rule::CostAboveRetail, "product P002"),
+                (Rule::UnresolvedBrand, "product P003"),
+                (Rule::UnknownDisplayCategory, "product P003"),
+                (Rule::MissingImage, "product P003"),
+            ]
+        );
+        assert!(report.ensure_no_errors().is_err());
+
+        let clean = validate(&brands, &categories, &products[..1]);
+        assert!(clean.issues.is_empty());
+        assert!(clean.ensure_no_errors().is_ok());
+    }
+}
@@ -2,7 +2,9 @@ use anyhow::{Context, Result};
 use serde::{Deserialize, Serialize};
 use std::path::{Path, PathBuf};
 
-use crate::feishu::auth::FeishuAuth;
+use crate::columns::ColumnMap;
+use crate::feishu::drive::DriveClient;
+use crate::models::bitable_value::RecordFields;
 
 /// Metadata about a processed video, stored alongside HLS output for cache invalidation
 #[derive(Debug, Serialize, Deserialize)]
@@ -20,22 +22,6 @@ pub struct AttachmentInfo {
     pub size: u64,
 }
 
-/// Extract full attachment info from a bitable record field
-pub fn extract_attachment_info(
-    fields: &std::collections::HashMap<String, serde_json::Value>,
-    key: &str,
-) -> Option<AttachmentInfo> {
-    let val = fields.get(key)?;
-    let arr = val.as_array()?;
-    let first = arr.first()?;
-
-    Some(AttachmentInfo {
-        file_token: first.get("file_token")?.as_str()?.to_string(),
-        name: first.get("name")?.as_str()?.to_string(),
-        size: first.get("size")?.as_u64()?,
-    })
-}
-
 /// Normalize media type: accept both Chinese and English values
 /// "视频" / "video" -> "video"
 /// "图片" / "image" / anything else -> "image"
@@ -60,89 +46,31 @@ pub struct RawMediaItem {
 /// Parse a bitable record into a RawMediaItem
 pub fn parse_raw_media_item(
     fields: &std::collections::HashMap<String, serde_json::Value>,
+    cols: &ColumnMap,
 ) -> Result<RawMediaItem> {
-    use crate::models::bitable_records::*;
-
-    let attachment = extract_attachment_info(fields, "文件");
-
-    if attachment.is_none() {
-        anyhow::bail!("Media missing '文件' attachment");
-    }
+    let f = RecordFields::new(fields);
+    let col = |model| cols.get(model);
+
+    let attachment = f
+        .attachment(col("file"))?
+        .map(|a| AttachmentInfo {
+            file_token: a.file_token.clone(),
+            name: a.name.clone(),
+            size: a.size.unwrap_or(0),
+        })
+        .with_context(|| format!("Media missing '{}' attachment", col("file")))?;
 
-    let raw_type = extract_select(fields, "媒体类型").unwrap_or_else(|| "image".to_string());
+    let raw_type = f.single_select(col("media_type"))?.unwrap_or_else(|| "image".to_string());
 
     Ok(RawMediaItem {
         media_type: normalize_media_type(&raw_type).to_string(),
-        title: extract_text(fields, "标题"),
-        duration: extract_number(fields, "时长(ms)").map(|n| n as i64),
-        sort_order: extract_number(fields, "排序").unwrap_or(0.0) as i32,
-        attachment,
+        title: f.text(col("title"))?,
+        duration: f.number(col("duration"))?.map(|n| n as i64),
+        sort_order: f.number(col("sort_order"))?.unwrap_or(0.0) as i32,
+        attachment: Some(attachment),
     })
 }
 
-// ============================================================
-// Feishu Drive API: resolve file_token -> real download URL
-// ============================================================
-
-#[derive(Debug, Deserialize)]
-struct DriveApiResponse {
-    code: i32,
-    msg: String,
-    data: Option<TmpDownloadData>,
-}
-
-#[derive(Debug, Deserialize)]
-struct TmpDownloadData {
-    tmp_download_urls: Vec<TmpDownloadUrl>,
-}
-
-#[derive(Debug, Deserialize)]
-struct TmpDownloadUrl {
-    #[allow(dead_code)]
-    file_token: String,
-    tmp_download_url: String,
-}
-
-/// Resolve a file_token to a temporary download URL via Feishu Drive API.
-/// The returned URL is a direct download link valid for ~30 minutes.
-async fn resolve_download_url(auth: &FeishuAuth, file_token: &str) -> Result<String> {
-    let token = auth.get_token().await?;
-    let url = format!(
-        "https://open.feishu.cn/open-apis/drive/v1/medias/batch_get_tmp_download_url?file_tokens={}",
-        file_token
-    );
-
-    let client = reqwest::Client::new();
-    let resp = client
-        .get(&url)
-        .header("Authorization", format!("Bearer {}", token))
-        .send()
-        .await
-        .context("Failed to call batch_get_tmp_download_url")?
-        .json::<DriveApiResponse>()
-        .await
-        .context("Failed to parse download URL response")?;
-
-    if resp.code != 0 {
-        anyhow::bail!(
-            "Failed to get download URL for {}: {} - {}",
-            file_token,
-            resp.code,
-            resp.msg
-        );
-    }
-
-    let data = resp.data.context("No data in download URL response")?;
-    let dl = data
-        .tmp_download_urls
-        .into_iter()
-        .next()
-        .context("Empty tmp_download_urls array")?;
-
-    tracing::debug!("Resolved {} -> download URL", file_token);
-    Ok(dl.tmp_download_url)
-}
-
 // ============================================================
 // Slug / filesystem helpers
 // ============================================================
@@ -195,7 +123,7 @@ async fn download_file(url: &str, dest: &Path) -> Result<u64> {
         .get(url)
         .send()
         .await
-        .with_context(|| format!("Failed to download from URL"))?;
+        .context("Failed to download from URL")?;
 
     if !resp.status().is_success() {
         anyhow::bail!("Download failed with status {}", resp.status());
@@ -299,7 +227,7 @@ fn write_meta(meta_path: &Path, file_token: &str, size: u64, source_name: &str)
 /// Process a single video: resolve download URL, download, convert to HLS.
 /// Returns the relative URL path to the m3u8 playlist.
 async fn process_one_video(
-    auth: &FeishuAuth,
+    drive: &DriveClient,
     attachment: &AttachmentInfo,
     slug: &str,
     videos_dir: &Path,
@@ -324,7 +252,7 @@ async fn process_one_video(
         &attachment.file_token,
         attachment.size as f64 / 1_048_576.0
     );
-    let download_url = resolve_download_url(auth, &attachment.file_token).await?;
+    let download_url = drive.tmp_download_url(&attachment.file_token).await?;
 
     // Step 2: Download to temp file
     let tmp_dir = std::env::temp_dir().join("bitable-sync-videos");
@@ -368,7 +296,7 @@ async fn process_one_video(
 /// Process all video media items: download attachments from Feishu, convert to HLS.
 /// Returns a list of final MediaItems with correct URLs.
 pub async fn process_media_items(
-    auth: &FeishuAuth,
+    drive: &DriveClient,
     raw_items: Vec<RawMediaItem>,
     public_dir: &Path,
 ) -> Result<Vec<crate::models::mock_data::MediaItem>> {
@@ -384,7 +312,7 @@ pub async fn process_media_items(
                         .as_deref()
                         .unwrap_or(&att.name.replace('.', "-")),
                 );
-                match process_one_video(auth, att, &slug, &videos_dir).await {
+                match process_one_video(drive, att, &slug, &videos_dir).await {
                     Ok(hls_url) => crate::models::mock_data::MediaItem {
                         media_type: "video".to_string(),
                         url: hls_url,
@@ -420,7 +348,7 @@ pub async fn process_media_items(
                 let ext = att.name.rsplit('.').next().unwrap_or("jpg");
                 let dest = images_dir.join(format!("{}.{}", slug, ext));
                 if !dest.exists() {
-                    match download_image_attachment(auth, &att.file_token, &dest).await {
+                    match download_image_attachment(drive, &att.file_token, &dest).await {
                         Ok(()) => {
                             tracing::info!("Downloaded media image '{}'", slug);
                         }
@@ -476,12 +404,12 @@ pub async fn process_media_items(
 /// Returns the relative path from public/ (e.g. "images/qrcode.jpg").
 /// Skips download if the destination file already exists and file_token hasn't changed.
 pub async fn download_image_attachment(
-    auth: &FeishuAuth,
+    drive: &DriveClient,
     file_token: &str,
     dest_path: &Path,
 ) -> Result<()> {
     // Resolve the temporary download URL
-    let dl_url = resolve_download_url(auth, file_token).await?;
+    let dl_url = drive.tmp_download_url(file_token).await?;
     download_file(&dl_url, dest_path).await?;
     tracing::info!("Downloaded image attachment to {}", dest_path.display());
     Ok(())